@@ -0,0 +1,323 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::{Duration, Instant};
+
+use ffxiv_crafting::{Actions, Attributes, Recipe, Status};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enhancer::{apply_enhancers, Enhancer},
+    new_status, simulate,
+    solver::{
+        registry::{self, SolverConfig},
+        CancelToken,
+    },
+};
+
+/// 需要达成的制作目标
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum Target {
+    /// 品质推满，即100%HQ
+    HighQuality,
+    /// 收藏价值达到指定的数值
+    Collectability(u32),
+}
+
+impl Target {
+    fn quality(&self, recipe: &Recipe) -> u32 {
+        match *self {
+            Target::HighQuality => recipe.quality,
+            Target::Collectability(threshold) => (threshold * 10).min(recipe.quality),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EnhancerPlan {
+    pub meal: Option<Enhancer>,
+    pub medicine: Option<Enhancer>,
+    pub attributes: Attributes,
+    pub actions: Vec<Actions>,
+    /// 按求解结果模拟得到的最终状态
+    pub status: Status,
+    pub reached: bool,
+}
+
+/// 自动选择食药时的目标、使用的求解器和求解预算
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OptimizeOptions {
+    pub target: Target,
+    pub solver: SolverConfig,
+    pub budget: Budget,
+}
+
+/// 求解预算，不使用食药和使用全部候选中最高属性的两次求解不计入预算
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    /// 最多为多少个组合调用求解器
+    pub max_solves: usize,
+    /// 超过该毫秒数后不再尝试新的组合。wasm中无法计时，只能为`None`
+    pub time_limit_ms: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct OptimizeResult {
+    /// 不使用任何食物和药水时的求解结果
+    pub without: EnhancerPlan,
+    /// 能达成目标的最便宜的组合，没有任何组合能达成目标时为`None`
+    pub best: Option<EnhancerPlan>,
+    /// 预算用尽时仍有未尝试的组合，此时`best`为`None`并不代表无法达成目标
+    pub exhausted: bool,
+}
+
+/// 寻找能够达成制作目标的最便宜的食物和药水组合。
+///
+/// 属性更高的组合在实践中总是不差于属性更低的组合，因此先去掉被更便宜的组合支配的候选，
+/// 剩下的候选按使用的数量、HQ的数量、总等级从低到高依次用指定的求解器求解（支持目标品质的求解器以目标品质求解），
+/// 第一个能达成目标的组合即为结果，属性不超过某个已失败组合的候选也会被跳过。
+/// 被`cancel`中止时返回错误
+pub fn optimize_enhancers(
+    attributes: Attributes,
+    recipe: Recipe,
+    meals: &[Enhancer],
+    medicines: &[Enhancer],
+    options: &OptimizeOptions,
+    cancel: &CancelToken,
+) -> Result<OptimizeResult, String> {
+    let OptimizeOptions {
+        target,
+        solver,
+        budget,
+    } = options;
+    let target_quality = target.quality(&recipe);
+    let evaluate = |meal: Option<&Enhancer>, medicine: Option<&Enhancer>, attributes| {
+        let status = new_status(attributes, recipe)?;
+        let config = solver.clone().with_target_quality(target_quality);
+        let actions = registry::solve(&config, status.clone(), cancel, &())?;
+        if cancel.is_cancelled() {
+            return Err(String::from("solve-cancelled"));
+        }
+        let status = simulate(status, actions.clone()).status;
+        let reached =
+            status.progress >= status.recipe.difficulty && status.quality >= target_quality;
+        Ok::<_, String>(EnhancerPlan {
+            meal: meal.cloned(),
+            medicine: medicine.cloned(),
+            attributes,
+            actions,
+            status,
+            reached,
+        })
+    };
+
+    let without = evaluate(None, None, attributes)?;
+    if without.reached {
+        return Ok(OptimizeResult {
+            best: Some(without.clone()),
+            without,
+            exhausted: false,
+        });
+    }
+
+    let candidates = candidates(attributes, meals, medicines);
+    // 所有候选中每项属性的最大值都无法达成目标时，不必逐一尝试
+    let upper_bound = candidates
+        .iter()
+        .fold(attributes, |a, &(_, _, b)| Attributes {
+            craftsmanship: a.craftsmanship.max(b.craftsmanship),
+            control: a.control.max(b.control),
+            craft_points: a.craft_points.max(b.craft_points),
+            ..a
+        });
+    if candidates.is_empty() || !evaluate(None, None, upper_bound)?.reached {
+        return Ok(OptimizeResult {
+            without,
+            best: None,
+            exhausted: false,
+        });
+    }
+
+    let start = budget
+        .time_limit_ms
+        .map(|ms| (Instant::now(), Duration::from_millis(ms)));
+    let mut solves = 0;
+    let mut failed = vec![attributes];
+    for (meal, medicine, attrs) in candidates {
+        if dominated(&attrs, &failed) {
+            continue;
+        }
+        let timeout = start.is_some_and(|(start, limit)| start.elapsed() >= limit);
+        if solves >= budget.max_solves || timeout {
+            return Ok(OptimizeResult {
+                without,
+                best: None,
+                exhausted: true,
+            });
+        }
+        solves += 1;
+        let plan = evaluate(meal, medicine, attrs)?;
+        if plan.reached {
+            return Ok(OptimizeResult {
+                without,
+                best: Some(plan),
+                exhausted: false,
+            });
+        }
+        failed.push(attrs);
+    }
+    Ok(OptimizeResult {
+        without,
+        best: None,
+        exhausted: false,
+    })
+}
+
+type Candidate<'a> = (Option<&'a Enhancer>, Option<&'a Enhancer>, Attributes);
+
+/// 列出至少使用一种食药的所有组合，按价格从低到高排列，
+/// 并去掉属性没有超过基础属性或任何一个更便宜的组合的候选
+fn candidates<'a>(
+    attributes: Attributes,
+    meals: &'a [Enhancer],
+    medicines: &'a [Enhancer],
+) -> Vec<Candidate<'a>> {
+    let meals = meals.iter().map(Some).chain([None]);
+    let mut candidates: Vec<_> = meals
+        .flat_map(|meal| {
+            medicines
+                .iter()
+                .map(Some)
+                .chain([None])
+                .map(move |m| (meal, m))
+        })
+        .filter(|c| c.0.is_some() || c.1.is_some())
+        .map(|(meal, medicine)| {
+            let enhancers: Vec<_> = meal.into_iter().chain(medicine).cloned().collect();
+            let attrs = apply_enhancers(attributes, &enhancers).attributes;
+            (meal, medicine, attrs)
+        })
+        .collect();
+    candidates.sort_by_key(|&(meal, medicine, _)| {
+        let used = [meal, medicine].into_iter().flatten();
+        (
+            used.clone().count(),
+            used.clone().filter(|e| e.is_hq).count(),
+            used.map(|e| e.level).sum::<u32>(),
+        )
+    });
+
+    let mut kept = vec![attributes];
+    candidates.retain(|&(_, _, attrs)| {
+        let keep = !dominated(&attrs, &kept);
+        if keep {
+            kept.push(attrs);
+        }
+        keep
+    });
+    candidates
+}
+
+/// `attrs`的每项属性都不超过`others`中的某一个
+fn dominated(attrs: &Attributes, others: &[Attributes]) -> bool {
+    others.iter().any(|o| {
+        attrs.craftsmanship <= o.craftsmanship
+            && attrs.control <= o.control
+            && attrs.craft_points <= o.craft_points
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::Recipe;
+
+    use super::{candidates, optimize_enhancers, Budget, OptimizeOptions, Target};
+    use crate::{
+        enhancer::Enhancer,
+        solver::{registry::SolverConfig, CancelToken},
+        test_status,
+    };
+
+    fn enhancer(name: &str, level: u32, is_hq: bool, ct: i32, cp: i32) -> Enhancer {
+        Enhancer {
+            name: name.to_string(),
+            level,
+            is_hq,
+            ct: Some(5),
+            ct_max: Some(ct),
+            cp: Some(20),
+            cp_max: Some(cp),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dominated_candidates_are_pruned() {
+        let meals = [
+            enhancer("cheap", 560, false, 40, 40),
+            // 比cheap更贵，属性却不更高
+            enhancer("worse", 610, true, 30, 40),
+            enhancer("better", 640, true, 90, 80),
+        ];
+        let medicines = [enhancer("tincture", 600, false, 0, 0)];
+        let names: Vec<_> = candidates(test_status().attributes, &meals, &medicines)
+            .into_iter()
+            .map(|(meal, medicine, _)| {
+                (
+                    meal.map(|e| e.name.as_str()),
+                    medicine.map(|e| e.name.as_str()),
+                )
+            })
+            .collect();
+        // 不提供任何加成的药水单独使用或与食物搭配都不会更好
+        assert_eq!(names, [(Some("cheap"), None), (Some("better"), None)]);
+    }
+
+    #[test]
+    fn unreachable_target() {
+        let status = test_status();
+        let recipe = Recipe {
+            difficulty: 60000,
+            ..status.recipe
+        };
+        let meals = [enhancer("better", 640, true, 90, 80)];
+        let options = OptimizeOptions {
+            target: Target::HighQuality,
+            solver: SolverConfig::Reflect {
+                use_manipulation: true,
+                use_waste_not: 0,
+                use_observe: false,
+            },
+            budget: Budget {
+                max_solves: 10,
+                time_limit_ms: Some(60000),
+            },
+        };
+        let result = optimize_enhancers(
+            status.attributes,
+            recipe,
+            &meals,
+            &[],
+            &options,
+            &CancelToken::new(),
+        )
+        .unwrap();
+        assert!(!result.without.reached);
+        assert!(result.best.is_none());
+        assert!(!result.exhausted);
+    }
+}
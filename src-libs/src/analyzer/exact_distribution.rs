@@ -0,0 +1,282 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, HashMap};
+
+use ffxiv_crafting::{Actions, Status};
+use serde::Serialize;
+
+use super::rand_simulations::CollectablesShopRefine;
+use crate::{next_conditions, rotation::Rotation};
+
+#[derive(Default, Serialize, Debug, Clone, PartialEq)]
+pub struct Distribution {
+    // 发生技能错误的概率
+    pub errors: f64,
+    // 技能模拟完成后仍处于制作状态的概率
+    pub unfinished: f64,
+    // 进展未推满的概率
+    pub fails: f64,
+    // 进展推满但没有出HQ的概率
+    pub normal: f64,
+    // 进展推满且出HQ的概率
+    pub highqual: f64,
+    // 进展推满时各个最终品质出现的概率，按品质从低到高排列
+    pub quality: Vec<QualityProbability>,
+    // 各个收藏价值档位的概率，只在传入收藏品信息时计算
+    pub collectability: Option<CollectabilityDistribution>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct QualityProbability {
+    pub quality: u32,
+    pub probability: f64,
+}
+
+#[derive(Default, Serialize, Debug, Clone, PartialEq)]
+pub struct CollectabilityDistribution {
+    pub no_collectability: f64,
+    pub low_collectability: f64,
+    pub middle_collectability: f64,
+    pub high_collectability: f64,
+}
+
+/// 用于合并相同状态的键，包含了影响后续模拟的全部信息
+#[derive(PartialEq, Eq, Hash)]
+pub(crate) struct StateKey {
+    pc: usize,
+    progress: u32,
+    quality: u32,
+    durability: u32,
+    craft_points: i32,
+    step: i32,
+    condition: u8,
+    buffs: [u8; 15],
+}
+
+impl StateKey {
+    pub(crate) fn new(pc: usize, s: &Status) -> Self {
+        let b = &s.buffs;
+        Self {
+            pc,
+            progress: s.progress as u32,
+            quality: s.quality,
+            durability: s.durability as u32,
+            craft_points: s.craft_points,
+            step: s.step,
+            condition: s.condition as u8,
+            buffs: [
+                b.muscle_memory,
+                b.great_strides,
+                b.veneration,
+                b.innovation,
+                b.inner_quiet,
+                b.final_appraisal,
+                b.manipulation,
+                b.wast_not,
+                b.heart_and_soul as u8,
+                b.trained_perfection as u8,
+                b.careful_observation_used,
+                b.quick_innovation_used,
+                b.touch_combo_stage,
+                b.observed,
+                b.expedience,
+            ],
+        }
+    }
+}
+
+/// 精确计算以指定初始状态执行技能序列的结果分布。
+///
+/// 技能的成败与球色的变化构成一个马尔可夫链，这里逐步展开所有可能的状态，
+/// 并把完全相同的状态合并以控制状态数量。与[`super::rand_simulations::stat`]中的随机模拟相对应，
+/// `ignore_errors`为真时跳过无法释放的技能，否则将其计入`errors`，制作结束后剩余的技能也是如此
+pub fn exact_distribution(
+    status: Status,
+    rotation: &Rotation,
+    ignore_errors: bool,
+    collectables_shop_refine: Option<&CollectablesShopRefine>,
+) -> Distribution {
+    let mut result = Distribution::default();
+    let mut qualities = BTreeMap::new();
+    let mut states = HashMap::from([(StateKey::new(0, &status), (0, status, 1.0))]);
+    while !states.is_empty() {
+        let mut next_states = HashMap::with_capacity(states.len());
+        let mut push = |pc: usize, s: Status, p: f64| {
+            next_states
+                .entry(StateKey::new(pc, &s))
+                .and_modify(|e: &mut (usize, Status, f64)| e.2 += p)
+                .or_insert((pc, s, p));
+        };
+        for (_, (mut pc, s, p)) in states {
+            let Some(action) = rotation.next_action(&mut pc, &s) else {
+                finish(&mut result, &mut qualities, &s, p);
+                continue;
+            };
+            if s.is_action_allowed(action).is_err() {
+                if ignore_errors {
+                    push(pc, s, p);
+                } else {
+                    result.errors += p;
+                }
+                continue;
+            }
+            // 制作结束后不立即计入结果，剩余的技能与随机模拟一样交由`is_action_allowed`检查
+            for (s, q) in transitions(&s, action) {
+                push(pc, s, p * q);
+            }
+        }
+        states = next_states;
+    }
+
+    result.quality = qualities
+        .into_iter()
+        .map(|(quality, probability)| QualityProbability {
+            quality,
+            probability,
+        })
+        .collect();
+    result.collectability = collectables_shop_refine.map(|refine| {
+        let mut dist = CollectabilityDistribution::default();
+        for q in &result.quality {
+            match refine.tier(q.quality) {
+                3 => dist.high_collectability += q.probability,
+                2 => dist.middle_collectability += q.probability,
+                1 => dist.low_collectability += q.probability,
+                _ => dist.no_collectability += q.probability,
+            }
+        }
+        dist
+    });
+    result
+}
+
+/// 将一个不再继续模拟的状态计入结果
+fn finish(result: &mut Distribution, qualities: &mut BTreeMap<u32, f64>, s: &Status, p: f64) {
+    if !s.is_finished() {
+        result.unfinished += p;
+    } else if s.progress < s.recipe.difficulty {
+        result.fails += p;
+    } else {
+        match s.high_quality_probability() {
+            None => result.errors += p,
+            Some(hq) => {
+                let hq = hq as f64 / 100.0;
+                result.highqual += p * hq;
+                result.normal += p * (1.0 - hq);
+                *qualities.entry(s.quality).or_insert(0.0) += p;
+            }
+        }
+    }
+}
+
+/// 列出在状态`s`下释放技能`action`后可能到达的所有状态及其概率，
+/// 包括技能的成败和下一回合球色的变化。调用前需要确认技能可以释放
+pub(crate) fn transitions(s: &Status, action: Actions) -> Vec<(Status, f64)> {
+    let success_rate = (s.success_rate(action) as f64 / 100.0).min(1.0);
+    let mut outcomes = vec![(action, success_rate)];
+    if success_rate < 1.0 {
+        outcomes.push((fail_action(action), 1.0 - success_rate));
+    }
+    let mut result = Vec::new();
+    for (cast, q) in outcomes {
+        let mut s = s.clone();
+        s.cast_action(cast);
+        let conditions = if matches!(action, Actions::FinalAppraisal | Actions::HeartAndSoul) {
+            vec![(s.condition, 1.0)]
+        } else {
+            next_conditions(&s, false)
+        };
+        for (condition, r) in conditions {
+            let mut s = s.clone();
+            s.condition = condition;
+            result.push((s, q * r));
+        }
+    }
+    result
+}
+
+fn fail_action(action: Actions) -> Actions {
+    match action {
+        Actions::RapidSynthesis => Actions::RapidSynthesisFail,
+        Actions::HastyTouch => Actions::HastyTouchFail,
+        Actions::DaringTouch => Actions::DaringTouchFail,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::Actions;
+
+    use super::exact_distribution;
+    use crate::{analyzer::rand_simulations::stat, rotation::Rotation, test_status};
+
+    #[test]
+    fn probabilities_sum_to_one() {
+        let actions = [
+            Actions::MuscleMemory,
+            Actions::Manipulation,
+            Actions::Veneration,
+            Actions::WasteNotII,
+            Actions::Groundwork,
+            Actions::HastyTouch,
+            Actions::HastyTouch,
+            Actions::PreparatoryTouch,
+            Actions::Innovation,
+            Actions::PrudentTouch,
+            Actions::GreatStrides,
+            Actions::ByregotsBlessing,
+            Actions::CarefulSynthesis,
+        ];
+        let dist = exact_distribution(test_status(), &Rotation::from(&actions[..]), true, None);
+        let total = dist.errors + dist.unfinished + dist.fails + dist.normal + dist.highqual;
+        assert!((total - 1.0).abs() < 1e-9, "total = {total}");
+        let finished: f64 = dist.quality.iter().map(|q| q.probability).sum();
+        assert!((finished - dist.normal - dist.highqual).abs() < 1e-9);
+    }
+
+    #[test]
+    fn trailing_actions_match_rand_simulations() {
+        // 耐久在第四次坯料制作后耗尽，之后的技能都是多余的
+        let actions = [
+            Actions::Groundwork,
+            Actions::Groundwork,
+            Actions::Groundwork,
+            Actions::Groundwork,
+            Actions::BasicTouch,
+            Actions::BasicTouch,
+        ];
+        const N: usize = 2000;
+        for ignore_errors in [false, true] {
+            let dist = exact_distribution(
+                test_status(),
+                &Rotation::from(&actions[..]),
+                ignore_errors,
+                None,
+            );
+            let stats = stat(test_status(), &actions, N, ignore_errors, Some(0));
+            let rate = |count: i32| count as f64 / N as f64;
+            assert!((dist.errors - rate(stats.errors)).abs() < 1e-9);
+            assert!((dist.unfinished - rate(stats.unfinished)).abs() < 1e-9);
+            assert!((dist.fails - rate(stats.fails)).abs() < 1e-9);
+            let finished = dist.normal + dist.highqual;
+            assert!((finished - rate(stats.normal + stats.highqual)).abs() < 1e-9);
+        }
+        let dist = exact_distribution(test_status(), &Rotation::from(&actions[..]), false, None);
+        assert!((dist.errors - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,171 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ffxiv_crafting::{Actions, Status};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    rand_simulations::CollectablesShopRefine,
+    scope_of_application::{refrash_caches, simulate, with_craft_points},
+};
+
+/// 坐标轴上等间距的一组属性值，包含两端
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct Axis {
+    pub min: i32,
+    pub max: i32,
+    pub step: i32,
+}
+
+impl Axis {
+    fn values(&self) -> Vec<i32> {
+        (self.min..=self.max)
+            .step_by(self.step.max(1) as usize)
+            .collect()
+    }
+}
+
+/// 以某组属性按顺序执行技能序列得到的结果。
+/// 不传入收藏品信息时只会出现`Fail`、`Normal`和`HighQuality`，否则以收藏价值档位代替后两者
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    Fail,
+    Normal,
+    HighQuality,
+    NoCollectability,
+    LowCollectability,
+    MiddleCollectability,
+    HighCollectability,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FeasibilityMap {
+    pub craftsmanship: Vec<i32>,
+    pub control: Vec<i32>,
+    pub craft_points: i32,
+    /// `outcomes[i][j]`为作业精度取`craftsmanship[i]`、加工精度取`control[j]`时的结果
+    pub outcomes: Vec<Vec<Outcome>>,
+}
+
+/// 计算技能序列在作业精度×加工精度平面上的结果分布，制作力上限固定为`craft_points`，缺省时使用当前值。
+///
+/// 进展只取决于作业精度，因此每一列先判断能否推满进展；
+/// 在同一列中结果随加工精度单调不减，因此只需对每一档结果的边界做二分查找，
+/// 模拟次数约为列数×档位数×log(行数)，而不是逐格模拟
+pub fn feasibility_map(
+    status: Status,
+    actions: &[Actions],
+    craftsmanship: Axis,
+    control: Axis,
+    craft_points: Option<i32>,
+    collectables_shop_refine: Option<&CollectablesShopRefine>,
+) -> FeasibilityMap {
+    let craft_points = craft_points.unwrap_or(status.attributes.craft_points);
+    let status = with_craft_points(&status, craft_points);
+    let craftsmanship = craftsmanship.values();
+    let control = control.values();
+    let levels = match collectables_shop_refine {
+        Some(_) => &[
+            Outcome::NoCollectability,
+            Outcome::LowCollectability,
+            Outcome::MiddleCollectability,
+            Outcome::HighCollectability,
+        ][..],
+        None => &[Outcome::Normal, Outcome::HighQuality][..],
+    };
+    let outcome = |cm: i32, ct: i32| {
+        let mut s = status.clone();
+        s.attributes.craftsmanship = cm;
+        s.attributes.control = ct;
+        refrash_caches(&mut s);
+        let s = simulate(s, actions);
+        if s.progress < s.recipe.difficulty {
+            Outcome::Fail
+        } else if let Some(refine) = collectables_shop_refine {
+            levels[refine.tier(s.quality) as usize]
+        } else if s.quality >= s.recipe.quality {
+            Outcome::HighQuality
+        } else {
+            Outcome::Normal
+        }
+    };
+
+    let mut outcomes = vec![vec![Outcome::Fail; control.len()]; craftsmanship.len()];
+    if let Some(&first) = control.first() {
+        // 进展随作业精度单调，先二分找到能推满进展的第一列
+        let passes = craftsmanship.partition_point(|&cm| outcome(cm, first) == Outcome::Fail);
+        let indices: Vec<usize> = (0..control.len()).collect();
+        for (column, &cm) in outcomes.iter_mut().zip(&craftsmanship).skip(passes) {
+            let base = outcome(cm, first);
+            column.fill(base);
+            // 依次二分查找每一档结果在这一列中最早出现的位置
+            let mut start = 0;
+            for &level in levels.iter().filter(|&&level| level > base) {
+                start += indices[start..].partition_point(|&j| outcome(cm, control[j]) < level);
+                column[start..].fill(level);
+            }
+        }
+    }
+
+    FeasibilityMap {
+        craftsmanship,
+        control,
+        craft_points,
+        outcomes,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::Actions;
+
+    use super::{feasibility_map, Axis, Outcome};
+    use crate::test_status;
+
+    #[test]
+    fn monotonic_outcomes() {
+        let actions = [
+            Actions::MuscleMemory,
+            Actions::Manipulation,
+            Actions::Veneration,
+            Actions::WasteNotII,
+            Actions::Groundwork,
+            Actions::Groundwork,
+            Actions::PreparatoryTouch,
+            Actions::PreparatoryTouch,
+            Actions::Innovation,
+            Actions::PreparatoryTouch,
+            Actions::GreatStrides,
+            Actions::ByregotsBlessing,
+            Actions::CarefulSynthesis,
+        ];
+        let axis = |min, max| Axis { min, max, step: 50 };
+        let map = feasibility_map(
+            test_status(),
+            &actions,
+            axis(3000, 5000),
+            axis(2500, 4500),
+            None,
+            None,
+        );
+        assert_eq!(map.outcomes.len(), map.craftsmanship.len());
+        for column in &map.outcomes {
+            assert!(column.windows(2).all(|w| w[0] <= w[1]));
+        }
+        assert_eq!(map.outcomes[0].len(), map.control.len());
+        assert!(map.outcomes.last().unwrap().last() > Some(&Outcome::Fail));
+    }
+}
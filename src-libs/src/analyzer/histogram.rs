@@ -0,0 +1,167 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Serializer};
+
+/// 统计一组整数出现的频数。
+/// 序列化时输出频数表以及均值、标准差、百分位数等摘要
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Histogram {
+    counts: BTreeMap<i64, u64>,
+}
+
+impl Histogram {
+    pub fn add(&mut self, value: i64) {
+        *self.counts.entry(value).or_insert(0) += 1;
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        for (value, count) in other.counts {
+            *self.counts.entry(value).or_insert(0) += count;
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let sum: f64 = self.counts.iter().map(|(&v, &c)| v as f64 * c as f64).sum();
+        Some(sum / count as f64)
+    }
+
+    /// 总体标准差
+    pub fn std_dev(&self) -> Option<f64> {
+        let mean = self.mean()?;
+        let variance: f64 = self
+            .counts
+            .iter()
+            .map(|(&v, &c)| (v as f64 - mean).powi(2) * c as f64)
+            .sum::<f64>()
+            / self.count() as f64;
+        Some(variance.sqrt())
+    }
+
+    /// 使用最近秩法计算百分位数，`p`的取值范围为0~100
+    pub fn percentile(&self, p: f64) -> Option<i64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((p / 100.0 * count as f64).ceil() as u64).clamp(1, count);
+        let mut seen = 0;
+        self.counts.iter().find_map(|(&v, &c)| {
+            seen += c;
+            (seen >= rank).then_some(v)
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct HistogramSummary {
+    count: u64,
+    mean: Option<f64>,
+    std_dev: Option<f64>,
+    min: Option<i64>,
+    max: Option<i64>,
+    p5: Option<i64>,
+    p25: Option<i64>,
+    p50: Option<i64>,
+    p75: Option<i64>,
+    p95: Option<i64>,
+    // 按数值从小到大排列的频数表
+    buckets: Vec<(i64, u64)>,
+}
+
+impl Serialize for Histogram {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        HistogramSummary {
+            count: self.count(),
+            mean: self.mean(),
+            std_dev: self.std_dev(),
+            min: self.counts.keys().next().copied(),
+            max: self.counts.keys().next_back().copied(),
+            p5: self.percentile(5.0),
+            p25: self.percentile(25.0),
+            p50: self.percentile(50.0),
+            p75: self.percentile(75.0),
+            p95: self.percentile(95.0),
+            buckets: self.counts.iter().map(|(&v, &c)| (v, c)).collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// 二项分布比例的置信区间
+#[derive(Default, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceInterval {
+    pub rate: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl ConfidenceInterval {
+    /// 使用Wilson方法计算`n`次试验中成功`k`次的95%置信区间
+    pub fn wilson(k: u64, n: u64) -> Self {
+        if n == 0 {
+            return Self {
+                rate: 0.0,
+                lower: 0.0,
+                upper: 1.0,
+            };
+        }
+        const Z: f64 = 1.959964;
+        let n = n as f64;
+        let p = k as f64 / n;
+        let denominator = 1.0 + Z * Z / n;
+        let center = (p + Z * Z / (2.0 * n)) / denominator;
+        let half_width = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt() / denominator;
+        Self {
+            rate: p,
+            lower: (center - half_width).max(0.0),
+            upper: (center + half_width).min(1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ConfidenceInterval, Histogram};
+
+    #[test]
+    fn summary() {
+        let mut h = Histogram::default();
+        for v in 1..=100 {
+            h.add(v);
+        }
+        assert_eq!(h.count(), 100);
+        assert_eq!(h.mean(), Some(50.5));
+        assert_eq!(h.percentile(50.0), Some(50));
+        assert_eq!(h.percentile(95.0), Some(95));
+        assert_eq!(h.percentile(0.0), Some(1));
+        assert_eq!(Histogram::default().mean(), None);
+
+        let ci = ConfidenceInterval::wilson(50, 100);
+        assert!(ci.lower < 0.5 && 0.5 < ci.upper);
+        assert!((ci.lower - 0.4038).abs() < 1e-3);
+    }
+}
@@ -14,5 +14,10 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod enhancer_optimizer;
+pub mod exact_distribution;
+pub mod feasibility_map;
+pub mod histogram;
 pub mod rand_simulations;
 pub mod scope_of_application;
+pub mod stat_weights;
@@ -14,38 +14,116 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{simulate_one_step, SimulateOneStepResult};
+use super::histogram::{ConfidenceInterval, Histogram};
+use crate::{
+    new_rng, rotation::Rotation, simulate_rotation_one_step, SimulateOneStepResult, SimulationRng,
+};
 use ffxiv_crafting::{Actions, CastActionError, Status};
-use rand::{rng, Rng};
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 
+/// 每块模拟的次数，每块使用一个独立的随机数生成器
+const CHUNK_SIZE: usize = 1024;
+
+/// 把`n`次模拟分成固定大小的块，分给多个线程执行后合并结果。
+/// 每块的随机数生成器都由`seed`派生，因此结果只与种子有关，与线程数无关
+#[cfg(not(target_family = "wasm"))]
+fn run_chunks<T, F, M>(n: usize, seed: Option<u64>, f: F, merge: M) -> T
+where
+    T: Default + Send,
+    F: Fn(&mut SimulationRng, usize) -> T + Sync,
+    M: Fn(&mut T, T) + Sync,
+{
+    run_chunks_with_threads(n, seed, num_cpus::get(), f, merge)
+}
+
+/// 与[`run_chunks`]相同，但最多使用`threads`个线程
+#[cfg(not(target_family = "wasm"))]
+fn run_chunks_with_threads<T, F, M>(
+    n: usize,
+    seed: Option<u64>,
+    threads: usize,
+    f: F,
+    merge: M,
+) -> T
+where
+    T: Default + Send,
+    F: Fn(&mut SimulationRng, usize) -> T + Sync,
+    M: Fn(&mut T, T) + Sync,
+{
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let base: u64 = new_rng(seed).random();
+    let chunks = n.div_ceil(CHUNK_SIZE);
+    let next_chunk = AtomicUsize::new(0);
+    let threads = threads.clamp(1, chunks.max(1));
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut result = T::default();
+                    loop {
+                        let i = next_chunk.fetch_add(1, Ordering::Relaxed);
+                        if i >= chunks {
+                            break result;
+                        }
+                        let mut rng = SimulationRng::seed_from_u64(base.wrapping_add(i as u64));
+                        merge(&mut result, f(&mut rng, CHUNK_SIZE.min(n - i * CHUNK_SIZE)));
+                    }
+                })
+            })
+            .collect();
+        let mut result = T::default();
+        for worker in workers {
+            merge(&mut result, worker.join().unwrap());
+        }
+        result
+    })
+}
+
+#[cfg(target_family = "wasm")]
+fn run_chunks<T, F, M>(n: usize, seed: Option<u64>, f: F, merge: M) -> T
+where
+    T: Default,
+    F: Fn(&mut SimulationRng, usize) -> T,
+    M: Fn(&mut T, T),
+{
+    let base: u64 = new_rng(seed).random();
+    let mut result = T::default();
+    for i in 0..n.div_ceil(CHUNK_SIZE) {
+        let mut rng = SimulationRng::seed_from_u64(base.wrapping_add(i as u64));
+        merge(&mut result, f(&mut rng, CHUNK_SIZE.min(n - i * CHUNK_SIZE)));
+    }
+    result
+}
+
+/// 执行完整个技能序列，制作结束后剩余的技能同样视为无法释放的技能
 fn simulation(
     rng: &mut impl Rng,
     s: &mut Status,
-    actions: &[Actions],
+    rotation: &Rotation,
     ignore_errors: bool,
 ) -> Result<Vec<SimulateOneStepResult>, CastActionError> {
     let mut history = Vec::new();
-    for action in actions {
-        let result = simulate_one_step(s, *action, false, rng);
-        let is_success = if ignore_errors {
-            result.unwrap_or(false)
-        } else {
-            result?
+    let mut pc = 0;
+    loop {
+        let result = simulate_rotation_one_step(s, rotation, &mut pc, false, rng);
+        let is_success = match result {
+            Ok(Some((_action, is_success))) => is_success,
+            Ok(None) => break,
+            Err(_) if ignore_errors => false,
+            Err(err) => return Err(err),
         };
 
         history.push(SimulateOneStepResult {
             status: s.clone(),
             is_success,
         });
-        if s.is_finished() {
-            break;
-        }
     }
     Ok(history)
 }
 
-#[derive(Default, Serialize)]
+#[derive(Default, Serialize, Debug, PartialEq)]
 pub struct Statistics {
     // 发生技能错误的模拟频数
     pub errors: i32,
@@ -57,40 +135,107 @@ pub struct Statistics {
     pub normal: i32,
     // 进展推满，品质也推满的模拟频数
     pub highqual: i32,
+    // 出HQ的概率及其95%置信区间
+    pub highqual_rate: ConfidenceInterval,
+    // 进展推满的模拟的最终品质
+    pub quality: Histogram,
+    // 进展推满的模拟剩余的制作力
+    pub craft_points_left: Histogram,
+    // 进展推满的模拟剩余的耐久
+    pub durability_left: Histogram,
+    // 未发生技能错误的模拟所用的步数
+    pub steps: Histogram,
 }
 
-pub fn stat(status: Status, actions: &[Actions], n: usize, ignore_errors: bool) -> Statistics {
-    let mut rng: rand::prelude::ThreadRng = rng();
-    let results = std::iter::from_fn({
-        let mut rng = rng.clone();
-        move || {
-            let mut s = status.clone();
-            Some(simulation(&mut rng, &mut s, actions, ignore_errors).map(|res| (res, s)))
-        }
-    });
+/// 以指定初始状态重复模拟`n`次技能序列，统计制作结果。
+/// 指定`seed`时，相同的种子总能得到相同的统计结果
+pub fn stat(
+    status: Status,
+    actions: &[Actions],
+    n: usize,
+    ignore_errors: bool,
+    seed: Option<u64>,
+) -> Statistics {
+    stat_rotation(status, &Rotation::from(actions), n, ignore_errors, seed)
+}
+
+/// 与[`stat`]相同，但模拟的是带条件分支的技能序列
+pub fn stat_rotation(
+    status: Status,
+    rotation: &Rotation,
+    n: usize,
+    ignore_errors: bool,
+    seed: Option<u64>,
+) -> Statistics {
+    let mut statistics = run_chunks(
+        n,
+        seed,
+        |rng, n| stat_chunk(rng, n, &status, rotation, ignore_errors),
+        Statistics::merge,
+    );
+    statistics.highqual_rate = ConfidenceInterval::wilson(statistics.highqual as u64, n as u64);
+    statistics
+}
+
+/// 用`rng`模拟`n`次技能序列，得到一块模拟的统计结果
+fn stat_chunk(
+    rng: &mut SimulationRng,
+    n: usize,
+    status: &Status,
+    rotation: &Rotation,
+    ignore_errors: bool,
+) -> Statistics {
     let mut statistics = Statistics::default();
-    for result in results.take(n) {
-        match result {
+    for _ in 0..n {
+        let mut s = status.clone();
+        match simulation(rng, &mut s, rotation, ignore_errors) {
             Err(_cast_err) => {
                 statistics.errors += 1;
+                continue;
             }
-            Ok((_history, status)) if !status.is_finished() => {
+            Ok(_history) if !s.is_finished() => {
                 statistics.unfinished += 1;
             }
-            Ok((_history, status)) if status.progress < status.recipe.difficulty => {
+            Ok(_history) if s.progress < s.recipe.difficulty => {
                 statistics.fails += 1;
             }
-            Ok((_history, status)) => match status.high_quality_probability() {
-                None => statistics.errors += 1,
-                Some(p) if p > rng.random_range(0..100) => statistics.highqual += 1,
-                _ => statistics.normal += 1,
+            Ok(_history) => match s.high_quality_probability() {
+                None => {
+                    statistics.errors += 1;
+                    continue;
+                }
+                Some(p) => {
+                    if p > rng.random_range(0..100) {
+                        statistics.highqual += 1;
+                    } else {
+                        statistics.normal += 1;
+                    }
+                    statistics.quality.add(s.quality as i64);
+                    statistics.craft_points_left.add(s.craft_points as i64);
+                    statistics.durability_left.add(s.durability as i64);
+                }
             },
         }
+        statistics.steps.add(s.step as i64);
     }
     statistics
 }
 
-#[derive(Default, Serialize)]
+impl Statistics {
+    fn merge(&mut self, other: Self) {
+        self.errors += other.errors;
+        self.unfinished += other.unfinished;
+        self.fails += other.fails;
+        self.normal += other.normal;
+        self.highqual += other.highqual;
+        self.quality.merge(other.quality);
+        self.craft_points_left.merge(other.craft_points_left);
+        self.durability_left.merge(other.durability_left);
+        self.steps.merge(other.steps);
+    }
+}
+
+#[derive(Default, Serialize, Debug, PartialEq)]
 pub struct CollectableStatistics {
     // 发生技能错误的模拟频数
     pub errors: i32,
@@ -106,58 +251,386 @@ pub struct CollectableStatistics {
     pub middle_collectability: i32,
     // 收藏价值第三档
     pub high_collectability: i32,
+    // 每次制作的期望奖励，只在收藏品信息中包含奖励时计算
+    pub expected_reward: Option<ExpectedReward>,
 }
 
-#[derive(Default, Deserialize)]
+#[derive(Default, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ExpectedReward {
+    pub scrip: f64,
+    pub exp: f64,
+    pub points: f64,
+}
+
+/// 收藏价值的三档阈值
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CollectablesShopRefine {
     pub low_collectability: u32,
     pub mid_collectability: u32,
     pub high_collectability: u32,
 }
 
+/// 收藏品信息的来源，取值与配方表中的`CollectablesMetadataKey`一致
+#[derive(Default, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectablesMetadataKind {
+    #[default]
+    CollectablesShopRefine = 1,
+    HWDCrafterSupply = 2,
+    SatisfactionSupply = 3,
+    SharlayanCraftWorkSupply = 4,
+    CollectablesRefine = 5,
+}
+
+impl CollectablesMetadataKind {
+    pub fn from_key(key: u16) -> Option<Self> {
+        match key {
+            1 => Some(Self::CollectablesShopRefine),
+            2 => Some(Self::HWDCrafterSupply),
+            3 => Some(Self::SatisfactionSupply),
+            4 => Some(Self::SharlayanCraftWorkSupply),
+            5 => Some(Self::CollectablesRefine),
+            _ => None,
+        }
+    }
+}
+
+/// 收藏品每一档收藏价值对应的奖励
+#[derive(Default, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CollectableReward {
+    // 工票
+    pub scrip: u32,
+    // 经验值
+    pub exp: u32,
+    // 友好部族的友好度、伊修加德重建的技巧点数等
+    pub points: u32,
+}
+
+/// 任意一种收藏品信息，序列化后的阈值字段与[`CollectablesShopRefine`]保持一致
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CollectablesMetadata {
+    #[serde(default)]
+    pub kind: CollectablesMetadataKind,
+    #[serde(default)]
+    pub id: u32,
+    #[serde(flatten)]
+    pub thresholds: CollectablesShopRefine,
+    // 依次为第一至第三档的奖励
+    #[serde(default)]
+    pub rewards: Option<[CollectableReward; 3]>,
+}
+
+impl From<CollectablesShopRefine> for CollectablesMetadata {
+    fn from(thresholds: CollectablesShopRefine) -> Self {
+        Self {
+            thresholds,
+            ..Default::default()
+        }
+    }
+}
+
+/// 从各个收藏品信息表中读出的数据，各后端查询后统一转换为[`CollectablesMetadata`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollectablesMetadataRow {
+    /// CollectablesShopRefine或CollectablesRefine，只有三档收藏价值
+    Refine {
+        kind: CollectablesMetadataKind,
+        id: u32,
+        collectability: [u32; 3],
+    },
+    /// 伊修加德重建的三档评分，以及对应的HWDCrafterSupplyReward中的工票、经验值和技巧点数
+    HWDCrafterSupply {
+        id: u32,
+        rating: [u32; 3],
+        scrip: [u32; 3],
+        exp: [u32; 3],
+        points: [u32; 3],
+    },
+    /// 老主顾的三档收藏价值，以及对应的SatisfactionSupplyReward中的工票数量和满意度
+    SatisfactionSupply {
+        id: u32,
+        collectability: [u32; 3],
+        scrip: [u32; 3],
+        satisfaction: [u32; 3],
+    },
+    /// 萨雷安的交纳只有中、高两档收藏价值，达到中档即可交纳
+    SharlayanCraftWorkSupply { id: u32, collectability: [u32; 2] },
+}
+
+impl From<CollectablesMetadataRow> for CollectablesMetadata {
+    fn from(row: CollectablesMetadataRow) -> Self {
+        let rewards = |scrip: [u32; 3], exp: [u32; 3], points: [u32; 3]| {
+            Some([0, 1, 2].map(|i| CollectableReward {
+                scrip: scrip[i],
+                exp: exp[i],
+                points: points[i],
+            }))
+        };
+        let (kind, id, [low, mid, high], rewards) = match row {
+            CollectablesMetadataRow::Refine {
+                kind,
+                id,
+                collectability,
+            } => (kind, id, collectability, None),
+            CollectablesMetadataRow::HWDCrafterSupply {
+                id,
+                rating,
+                scrip,
+                exp,
+                points,
+            } => (
+                CollectablesMetadataKind::HWDCrafterSupply,
+                id,
+                rating,
+                rewards(scrip, exp, points),
+            ),
+            CollectablesMetadataRow::SatisfactionSupply {
+                id,
+                collectability,
+                scrip,
+                satisfaction,
+            } => (
+                CollectablesMetadataKind::SatisfactionSupply,
+                id,
+                collectability,
+                rewards(scrip, [0; 3], satisfaction),
+            ),
+            CollectablesMetadataRow::SharlayanCraftWorkSupply {
+                id,
+                collectability: [mid, high],
+            } => (
+                CollectablesMetadataKind::SharlayanCraftWorkSupply,
+                id,
+                [0, mid, high],
+                None,
+            ),
+        };
+        Self {
+            kind,
+            id,
+            thresholds: CollectablesShopRefine {
+                low_collectability: low,
+                mid_collectability: mid,
+                high_collectability: high,
+            },
+            rewards,
+        }
+    }
+}
+
+impl CollectablesShopRefine {
+    /// 计算品质对应的收藏价值档位，0表示无收藏价值，1~3依次为第一至第三档
+    pub(crate) fn tier(&self, quality: u32) -> u8 {
+        let collectability = quality / 10;
+        [
+            self.high_collectability,
+            self.mid_collectability,
+            self.low_collectability,
+        ]
+        .into_iter()
+        .position(|threshold| threshold > 0 && collectability >= threshold)
+        .map_or(0, |i| 3 - i as u8)
+    }
+}
+
+/// 以指定初始状态重复模拟`n`次技能序列，按收藏价值档位统计制作结果。
+/// 收藏品信息中包含各档奖励时，还会计算每次制作的期望奖励。
+/// 指定`seed`时，相同的种子总能得到相同的统计结果
 pub fn stat_collectables(
     status: Status,
     actions: &[Actions],
     n: usize,
     ignore_errors: bool,
-    collectables_shop_refine: CollectablesShopRefine,
+    collectables_metadata: impl Into<CollectablesMetadata>,
+    seed: Option<u64>,
 ) -> CollectableStatistics {
-    let mut rng = rng();
-    let results = std::iter::from_fn(|| {
-        let mut s = status.clone();
-        Some(simulation(&mut rng, &mut s, actions, ignore_errors).map(|res| (res, s)))
-    });
-    let mut statistics = CollectableStatistics::default();
-    for result in results.take(n) {
-        match result {
-            Err(_cast_err) => {
-                statistics.errors += 1;
-            }
-            Ok((_history, status)) => {
-                if !status.is_finished() {
-                    statistics.unfinished += 1;
-                } else if status.progress < status.recipe.difficulty {
-                    statistics.fails += 1;
-                } else {
-                    let collectability = status.quality / 10;
-                    if collectables_shop_refine.high_collectability > 0
-                        && collectability >= collectables_shop_refine.high_collectability
-                    {
-                        statistics.high_collectability += 1;
-                    } else if collectables_shop_refine.mid_collectability > 0
-                        && collectability >= collectables_shop_refine.mid_collectability
-                    {
-                        statistics.middle_collectability += 1;
-                    } else if collectables_shop_refine.low_collectability > 0
-                        && collectability >= collectables_shop_refine.low_collectability
-                    {
-                        statistics.low_collectability += 1;
-                    } else {
-                        statistics.no_collectability += 1;
+    stat_collectables_rotation(
+        status,
+        &Rotation::from(actions),
+        n,
+        ignore_errors,
+        collectables_metadata,
+        seed,
+    )
+}
+
+/// 与[`stat_collectables`]相同，但模拟的是带条件分支的技能序列
+pub fn stat_collectables_rotation(
+    status: Status,
+    rotation: &Rotation,
+    n: usize,
+    ignore_errors: bool,
+    collectables_metadata: impl Into<CollectablesMetadata>,
+    seed: Option<u64>,
+) -> CollectableStatistics {
+    let metadata = collectables_metadata.into();
+    let mut statistics = run_chunks(
+        n,
+        seed,
+        |rng, n| {
+            let mut statistics = CollectableStatistics::default();
+            for _ in 0..n {
+                let mut s = status.clone();
+                match simulation(rng, &mut s, rotation, ignore_errors) {
+                    Err(_cast_err) => {
+                        statistics.errors += 1;
                     }
+                    Ok(_history) if !s.is_finished() => {
+                        statistics.unfinished += 1;
+                    }
+                    Ok(_history) if s.progress < s.recipe.difficulty => {
+                        statistics.fails += 1;
+                    }
+                    Ok(_history) => match metadata.thresholds.tier(s.quality) {
+                        3 => statistics.high_collectability += 1,
+                        2 => statistics.middle_collectability += 1,
+                        1 => statistics.low_collectability += 1,
+                        _ => statistics.no_collectability += 1,
+                    },
                 }
             }
+            statistics
+        },
+        CollectableStatistics::merge,
+    );
+    if let Some(rewards) = &metadata.rewards {
+        let tiers = [
+            statistics.low_collectability,
+            statistics.middle_collectability,
+            statistics.high_collectability,
+        ];
+        let mut expected = ExpectedReward::default();
+        for (count, reward) in tiers.into_iter().zip(rewards) {
+            let p = count as f64 / n.max(1) as f64;
+            expected.scrip += p * reward.scrip as f64;
+            expected.exp += p * reward.exp as f64;
+            expected.points += p * reward.points as f64;
         }
+        statistics.expected_reward = Some(expected);
     }
     statistics
 }
+
+impl CollectableStatistics {
+    fn merge(&mut self, other: Self) {
+        self.errors += other.errors;
+        self.unfinished += other.unfinished;
+        self.fails += other.fails;
+        self.no_collectability += other.no_collectability;
+        self.low_collectability += other.low_collectability;
+        self.middle_collectability += other.middle_collectability;
+        self.high_collectability += other.high_collectability;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::Actions;
+
+    use super::{
+        run_chunks_with_threads, stat, stat_chunk, CollectablesMetadata, CollectablesMetadataKind,
+        CollectablesMetadataRow, Statistics,
+    };
+    use crate::{rotation::Rotation, test_status};
+
+    #[test]
+    fn same_seed_same_statistics() {
+        let actions = [
+            Actions::MuscleMemory,
+            Actions::Manipulation,
+            Actions::Veneration,
+            Actions::WasteNotII,
+            Actions::Groundwork,
+            Actions::Groundwork,
+            Actions::HastyTouch,
+            Actions::HastyTouch,
+            Actions::HastyTouch,
+            Actions::Innovation,
+            Actions::PreparatoryTouch,
+            Actions::PreparatoryTouch,
+            Actions::GreatStrides,
+            Actions::ByregotsBlessing,
+            Actions::CarefulSynthesis,
+        ];
+        let a = stat(test_status(), &actions, 1000, true, Some(42));
+        let b = stat(test_status(), &actions, 1000, true, Some(42));
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn same_seed_regardless_of_threads() {
+        let actions = [
+            Actions::MuscleMemory,
+            Actions::Veneration,
+            Actions::Groundwork,
+            Actions::HastyTouch,
+            Actions::HastyTouch,
+            Actions::RapidSynthesis,
+            Actions::RapidSynthesis,
+            Actions::BasicSynthesis,
+        ];
+        let (status, rotation) = (test_status(), Rotation::from(&actions[..]));
+        // 模拟次数不是块大小的整数倍，最后一块只模拟剩下的次数
+        let n = 5000;
+        let run = |threads| {
+            run_chunks_with_threads(
+                n,
+                Some(7),
+                threads,
+                |rng, n| stat_chunk(rng, n, &status, &rotation, false),
+                Statistics::merge,
+            )
+        };
+        let expected = run(1);
+        for threads in [2, 3, 8] {
+            assert_eq!(run(threads), expected, "threads = {threads}");
+        }
+    }
+
+    #[test]
+    fn collectables_metadata_rows() {
+        let hwd = CollectablesMetadata::from(CollectablesMetadataRow::HWDCrafterSupply {
+            id: 3,
+            rating: [650, 900, 1000],
+            scrip: [144, 180, 216],
+            exp: [10000, 20000, 30000],
+            points: [1300, 1600, 2000],
+        });
+        assert_eq!(hwd.kind, CollectablesMetadataKind::HWDCrafterSupply);
+        assert_eq!(hwd.thresholds.tier(9000), 2);
+        let rewards = hwd.rewards.unwrap();
+        assert_eq!(rewards[0].scrip, 144);
+        assert_eq!(rewards[1].exp, 20000);
+        assert_eq!(rewards[2].points, 2000);
+
+        let satisfaction =
+            CollectablesMetadata::from(CollectablesMetadataRow::SatisfactionSupply {
+                id: 1,
+                collectability: [50, 60, 70],
+                scrip: [10, 20, 30],
+                satisfaction: [50, 80, 100],
+            });
+        let rewards = satisfaction.rewards.unwrap();
+        assert_eq!(rewards[2].scrip, 30);
+        assert_eq!(rewards[2].exp, 0);
+        assert_eq!(rewards[2].points, 100);
+
+        // 没有最低档，未达到中档时无法交纳
+        let sharlayan =
+            CollectablesMetadata::from(CollectablesMetadataRow::SharlayanCraftWorkSupply {
+                id: 2,
+                collectability: [550, 800],
+            });
+        assert_eq!(sharlayan.rewards, None);
+        assert_eq!(sharlayan.thresholds.tier(5490), 0);
+        assert_eq!(sharlayan.thresholds.tier(5500), 2);
+        assert_eq!(sharlayan.thresholds.tier(8000), 3);
+
+        let refine = CollectablesMetadata::from(CollectablesMetadataRow::Refine {
+            kind: CollectablesMetadataKind::CollectablesRefine,
+            id: 4,
+            collectability: [400, 500, 600],
+        });
+        assert_eq!(refine.kind, CollectablesMetadataKind::CollectablesRefine);
+        assert_eq!(refine.thresholds.tier(4000), 1);
+    }
+}
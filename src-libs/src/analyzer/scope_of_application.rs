@@ -22,6 +22,22 @@ pub struct Scope {
     craftsmanship_range: (Option<i32>, Option<i32>),
     control_range: Option<i32>,
     craft_points: i32,
+    // 仍能得到相同制作结果的最低制作力上限
+    min_craft_points: i32,
+    // 加工精度超过该值后，品质在最后一次提升品质的技能之前就已经达到上限
+    control_upper_bound: Option<i32>,
+    // 每个技能的进展和品质增量保持不变的属性范围
+    steps: Vec<StepScope>,
+}
+
+/// 技能序列中的一个技能的进展和品质增量保持不变的属性范围，
+/// 上限为`None`表示在当前属性之上5000点内都不会改变
+#[derive(Default, Serialize)]
+pub struct StepScope {
+    progress: u32,
+    quality: u32,
+    craftsmanship_range: (i32, Option<i32>),
+    control_range: (i32, Option<i32>),
 }
 
 pub fn calc_scope(init_status: Status, actions: &[Actions]) -> Scope {
@@ -30,10 +46,13 @@ pub fn calc_scope(init_status: Status, actions: &[Actions]) -> Scope {
         craftsmanship_range: find_craftsmanship_range(&init_status, &final_status, actions),
         control_range: find_control_range(&init_status, &final_status, actions),
         craft_points: init_status.craft_points - final_status.craft_points,
+        min_craft_points: find_min_craft_points(&init_status, &final_status, actions),
+        control_upper_bound: find_control_upper_bound(&init_status, actions),
+        steps: find_step_ranges(&init_status, actions),
     }
 }
 
-fn simulate(mut status: Status, actions: &[Actions]) -> Status {
+pub(crate) fn simulate(mut status: Status, actions: &[Actions]) -> Status {
     for a in actions {
         if status.is_action_allowed(*a).is_ok() {
             status.cast_action(*a);
@@ -99,6 +118,249 @@ fn find_control_range(
     low
 }
 
-fn refrash_caches(status: &mut Status) {
+fn find_min_craft_points(init_status: &Status, final_status: &Status, actions: &[Actions]) -> i32 {
+    let same_result = |cp: i32| {
+        let status = simulate(with_craft_points(init_status, cp), actions);
+        status.progress == final_status.progress
+            && status.quality == final_status.quality
+            && status.step == final_status.step
+    };
+    if same_result(0) {
+        return 0;
+    }
+    // 制作力上限越高，能释放的技能只会更多，因此结果相同的制作力上限是一个区间
+    let init_craft_points = init_status.attributes.craft_points;
+    bisect(0, init_craft_points, |cp| !same_result(cp)) + 1
+}
+
+fn find_control_upper_bound(init_status: &Status, actions: &[Actions]) -> Option<i32> {
+    let deltas = simulate_deltas(init_status.clone(), actions);
+    let last_touch = deltas.iter().rposition(|&(_, quality)| quality > 0)?;
+    let below_max = |ct: i32| {
+        let mut status = init_status.clone();
+        status.attributes.control = ct;
+        refrash_caches(&mut status);
+        let status = simulate(status, &actions[..last_touch]);
+        status.quality < status.recipe.quality
+    };
+    if !below_max(0) {
+        return None;
+    }
+    let high = init_status.attributes.control + 5000;
+    if below_max(high) {
+        return None;
+    }
+    Some(bisect(0, high, below_max))
+}
+
+fn find_step_ranges(init_status: &Status, actions: &[Actions]) -> Vec<StepScope> {
+    let deltas = simulate_deltas(init_status.clone(), actions);
+    let (progress, quality): (Vec<_>, Vec<_>) = deltas.iter().copied().unzip();
+    let craftsmanship_ranges =
+        find_same_ranges(init_status.attributes.craftsmanship, &progress, |cm| {
+            let mut status = init_status.clone();
+            status.attributes.craftsmanship = cm;
+            refrash_caches(&mut status);
+            simulate_deltas(status, actions)
+                .into_iter()
+                .map(|d| d.0)
+                .collect()
+        });
+    let control_ranges = find_same_ranges(init_status.attributes.control, &quality, |ct| {
+        let mut status = init_status.clone();
+        status.attributes.control = ct;
+        refrash_caches(&mut status);
+        simulate_deltas(status, actions)
+            .into_iter()
+            .map(|d| d.1)
+            .collect()
+    });
+    deltas
+        .into_iter()
+        .zip(craftsmanship_ranges)
+        .zip(control_ranges)
+        .map(
+            |(((progress, quality), craftsmanship_range), control_range)| StepScope {
+                progress,
+                quality,
+                craftsmanship_range,
+                control_range,
+            },
+        )
+        .collect()
+}
+
+/// 从`init`开始向两侧逐个扫描属性值，找出每个技能的增量与`deltas`相同的、包含`init`的区间。
+/// 品质达到上限或提前完成制作时，增量并不随属性单调变化，因此不能二分查找
+fn find_same_ranges(
+    init: i32,
+    deltas: &[u32],
+    deltas_at: impl Fn(i32) -> Vec<u32>,
+) -> Vec<(i32, Option<i32>)> {
+    let mut ranges = vec![(0, None); deltas.len()];
+    let mut pending: Vec<usize> = (0..deltas.len()).collect();
+    for v in (0..init).rev() {
+        if pending.is_empty() {
+            break;
+        }
+        let current = deltas_at(v);
+        pending.retain(|&i| {
+            let same = current[i] == deltas[i];
+            if !same {
+                ranges[i].0 = v + 1;
+            }
+            same
+        });
+    }
+    let mut pending: Vec<usize> = (0..deltas.len()).collect();
+    for v in init + 1..=init + 5000 {
+        if pending.is_empty() {
+            break;
+        }
+        let current = deltas_at(v);
+        pending.retain(|&i| {
+            let same = current[i] == deltas[i];
+            if !same {
+                ranges[i].1 = Some(v - 1);
+            }
+            same
+        });
+    }
+    ranges
+}
+
+/// 在`[low, high]`中查找使`pred`成立的最大值，要求`pred(low)`成立，
+/// 且`pred`在区间上先成立后不成立
+fn bisect(mut low: i32, mut high: i32, pred: impl Fn(i32) -> bool) -> i32 {
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if pred(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
+
+/// 按顺序模拟技能序列，返回每个技能的进展和品质增量，无法释放的技能增量为0
+fn simulate_deltas(mut status: Status, actions: &[Actions]) -> Vec<(u32, u32)> {
+    let mut deltas = Vec::with_capacity(actions.len());
+    for a in actions {
+        let (progress, quality) = (status.progress, status.quality);
+        if !status.is_finished() && status.is_action_allowed(*a).is_ok() {
+            status.cast_action(*a);
+        }
+        deltas.push((
+            (status.progress - progress) as u32,
+            status.quality - quality,
+        ));
+    }
+    deltas
+}
+
+pub(crate) fn with_craft_points(init_status: &Status, craft_points: i32) -> Status {
+    let mut status = init_status.clone();
+    let delta = craft_points - status.attributes.craft_points;
+    status.attributes.craft_points = craft_points;
+    status.craft_points += delta;
+    status
+}
+
+pub(crate) fn refrash_caches(status: &mut Status) {
     status.caches = Caches::new(&status.attributes, &status.recipe);
 }
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::{Actions, Status};
+
+    use super::{
+        bisect, find_same_ranges, find_step_ranges, refrash_caches, simulate, simulate_deltas,
+    };
+    use crate::test_status;
+
+    #[test]
+    fn bisect_bounds() {
+        assert_eq!(bisect(0, 100, |v| v <= 42), 42);
+        assert_eq!(bisect(0, 100, |_| true), 100);
+        assert_eq!(bisect(0, 100, |v| v == 0), 0);
+    }
+
+    #[test]
+    fn same_ranges_are_contiguous() {
+        // 第二个技能的增量先增后减，与初始值相同的区间只取包含初始值的一段
+        let deltas_at = |v: i32| vec![(v / 10) as u32, (v - 50).unsigned_abs() / 10];
+        let ranges = find_same_ranges(50, &deltas_at(50), deltas_at);
+        assert_eq!(ranges, vec![(50, Some(59)), (41, Some(59))]);
+    }
+
+    /// 逐个检查每个技能的属性范围内增量都不变，而范围外相邻的属性值会改变增量
+    fn check_step_ranges(init_status: &Status, actions: &[Actions]) {
+        let deltas = simulate_deltas(init_status.clone(), actions);
+        let at = |f: &dyn Fn(&mut Status)| {
+            let mut status = init_status.clone();
+            f(&mut status);
+            refrash_caches(&mut status);
+            simulate_deltas(status, actions)
+        };
+        let cm_at = |cm| at(&|s: &mut Status| s.attributes.craftsmanship = cm);
+        let ct_at = |ct| at(&|s: &mut Status| s.attributes.control = ct);
+        for (i, step) in find_step_ranges(init_status, actions).iter().enumerate() {
+            let (low, high) = step.craftsmanship_range;
+            let end = high.unwrap_or(init_status.attributes.craftsmanship + 5000);
+            for cm in low..=end {
+                assert_eq!(cm_at(cm)[i].0, deltas[i].0, "step {i}, craftsmanship {cm}");
+            }
+            if low > 0 {
+                assert_ne!(cm_at(low - 1)[i].0, deltas[i].0);
+            }
+            if let Some(high) = high {
+                assert_ne!(cm_at(high + 1)[i].0, deltas[i].0);
+            }
+
+            let (low, high) = step.control_range;
+            let end = high.unwrap_or(init_status.attributes.control + 5000);
+            for ct in low..=end {
+                assert_eq!(ct_at(ct)[i].1, deltas[i].1, "step {i}, control {ct}");
+            }
+            if low > 0 {
+                assert_ne!(ct_at(low - 1)[i].1, deltas[i].1);
+            }
+            if let Some(high) = high {
+                assert_ne!(ct_at(high + 1)[i].1, deltas[i].1);
+            }
+        }
+    }
+
+    #[test]
+    fn step_ranges_at_quality_cap() {
+        // 品质上限很低，加工精度越高越早达到上限，之后的加工增量反而变小
+        let mut status = test_status();
+        status.recipe.quality = 800;
+        let actions = [
+            Actions::BasicTouch,
+            Actions::BasicTouch,
+            Actions::BasicTouch,
+            Actions::BasicTouch,
+            Actions::BasicSynthesis,
+        ];
+        assert!(simulate(status.clone(), &actions).quality >= status.recipe.quality);
+        check_step_ranges(&status, &actions);
+    }
+
+    #[test]
+    fn step_ranges_with_early_finish() {
+        // 作业精度越高越早推满进展，之后的技能不再释放，增量变为0
+        let mut status = test_status();
+        status.recipe.difficulty = 2000;
+        let actions = [
+            Actions::CarefulSynthesis,
+            Actions::CarefulSynthesis,
+            Actions::CarefulSynthesis,
+            Actions::CarefulSynthesis,
+            Actions::CarefulSynthesis,
+        ];
+        check_step_ranges(&status, &actions);
+    }
+}
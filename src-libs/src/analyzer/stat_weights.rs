@@ -0,0 +1,211 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::ops::Sub;
+
+use ffxiv_crafting::{Actions, Status};
+use serde::Serialize;
+
+use super::{
+    exact_distribution::exact_distribution,
+    rand_simulations::CollectablesShopRefine,
+    scope_of_application::{refrash_caches, with_craft_points},
+};
+use crate::rotation::Rotation;
+
+/// 评估属性变化时使用的制作方案
+pub enum Plan<'a> {
+    /// 属性变化后仍然使用同一个技能序列
+    Rotation(&'a Rotation),
+    /// 属性变化后用求解器重新求解技能序列
+    Solver(&'a dyn Fn(&Status) -> Vec<Actions>),
+}
+
+/// 以某组属性执行制作方案的期望结果
+#[derive(Default, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Evaluation {
+    // 推满进展的概率
+    pub success_rate: f64,
+    // 出HQ的概率
+    pub hq_rate: f64,
+    // 最终品质的期望，进展未推满时记为0
+    pub expected_quality: f64,
+    // 收藏价值档位（0~3）的期望，只在传入收藏品信息时计算
+    pub expected_collectability: Option<f64>,
+}
+
+impl Sub for Evaluation {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            success_rate: self.success_rate - rhs.success_rate,
+            hq_rate: self.hq_rate - rhs.hq_rate,
+            expected_quality: self.expected_quality - rhs.expected_quality,
+            expected_collectability: self
+                .expected_collectability
+                .zip(rhs.expected_collectability)
+                .map(|(a, b)| a - b),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct StatWeight {
+    // 增加属性后的期望结果
+    pub evaluation: Evaluation,
+    // 与当前属性相比的变化量
+    pub change: Evaluation,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StatWeights {
+    pub delta: i32,
+    pub base: Evaluation,
+    pub craftsmanship: StatWeight,
+    pub control: StatWeight,
+    pub craft_points: StatWeight,
+}
+
+/// 分别估计作业精度、加工精度和制作力上限增加`delta`点后制作结果的变化，用于判断下一颗魔晶石镶嵌哪种属性。
+///
+/// 制作结果按[`exact_distribution`]精确计算，因此考虑了球色和技能成败的随机性
+pub fn stat_weights(
+    status: Status,
+    plan: Plan,
+    delta: i32,
+    ignore_errors: bool,
+    collectables_shop_refine: Option<&CollectablesShopRefine>,
+) -> StatWeights {
+    let evaluate =
+        |status: Status| evaluate(status, &plan, ignore_errors, collectables_shop_refine);
+    let base = evaluate(status.clone());
+    let weight = |evaluation: Evaluation| StatWeight {
+        evaluation,
+        change: evaluation - base,
+    };
+
+    let mut s = status.clone();
+    s.attributes.craftsmanship += delta;
+    refrash_caches(&mut s);
+    let craftsmanship = weight(evaluate(s));
+
+    let mut s = status.clone();
+    s.attributes.control += delta;
+    refrash_caches(&mut s);
+    let control = weight(evaluate(s));
+
+    let craft_points = status.attributes.craft_points + delta;
+    let craft_points = weight(evaluate(with_craft_points(&status, craft_points)));
+
+    StatWeights {
+        delta,
+        base,
+        craftsmanship,
+        control,
+        craft_points,
+    }
+}
+
+fn evaluate(
+    status: Status,
+    plan: &Plan,
+    ignore_errors: bool,
+    collectables_shop_refine: Option<&CollectablesShopRefine>,
+) -> Evaluation {
+    let dist = match plan {
+        Plan::Rotation(rotation) => {
+            exact_distribution(status, rotation, ignore_errors, collectables_shop_refine)
+        }
+        Plan::Solver(solve) => {
+            let actions = solve(&status);
+            let rotation = Rotation::from(actions.as_slice());
+            exact_distribution(status, &rotation, ignore_errors, collectables_shop_refine)
+        }
+    };
+    Evaluation {
+        success_rate: dist.normal + dist.highqual,
+        hq_rate: dist.highqual,
+        expected_quality: dist
+            .quality
+            .iter()
+            .map(|q| q.quality as f64 * q.probability)
+            .sum(),
+        expected_collectability: dist.collectability.map(|c| {
+            c.low_collectability + 2.0 * c.middle_collectability + 3.0 * c.high_collectability
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::Actions;
+
+    use super::{stat_weights, Evaluation, Plan};
+    use crate::{rotation::Rotation, test_status};
+
+    const ACTIONS: [Actions; 12] = [
+        Actions::MuscleMemory,
+        Actions::Manipulation,
+        Actions::Veneration,
+        Actions::WasteNotII,
+        Actions::Groundwork,
+        Actions::Groundwork,
+        Actions::Innovation,
+        Actions::PreparatoryTouch,
+        Actions::PreparatoryTouch,
+        Actions::GreatStrides,
+        Actions::ByregotsBlessing,
+        Actions::CarefulSynthesis,
+    ];
+
+    #[test]
+    fn zero_delta_changes_nothing() {
+        let rotation = Rotation::from(&ACTIONS[..]);
+        let weights = stat_weights(test_status(), Plan::Rotation(&rotation), 0, true, None);
+        assert_eq!(weights.craftsmanship.change, Evaluation::default());
+        assert_eq!(weights.control.change, Evaluation::default());
+        assert_eq!(weights.craft_points.evaluation, weights.base);
+    }
+
+    #[test]
+    fn control_raises_quality_and_missing_craft_points_fail() {
+        let mut status = test_status();
+        status.recipe.difficulty = 1500;
+        // 共消耗281制作力，结束时进展推满而品质远未达到上限
+        let rotation = Rotation::from(
+            &[
+                Actions::MuscleMemory,
+                Actions::Manipulation,
+                Actions::Innovation,
+                Actions::PreparatoryTouch,
+                Actions::PreparatoryTouch,
+                Actions::GreatStrides,
+                Actions::ByregotsBlessing,
+                Actions::Groundwork,
+                Actions::CarefulSynthesis,
+            ][..],
+        );
+        let more = stat_weights(status.clone(), Plan::Rotation(&rotation), 100, false, None);
+        assert!(more.base.success_rate > 0.999);
+        assert!(more.control.change.expected_quality > 0.0);
+        assert_eq!(more.control.change.success_rate, 0.0);
+
+        let less = stat_weights(status, Plan::Rotation(&rotation), -500, false, None);
+        assert_eq!(less.craft_points.evaluation.success_rate, 0.0);
+        assert!(less.craft_points.change.success_rate < -0.999);
+    }
+}
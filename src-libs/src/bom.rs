@@ -0,0 +1,300 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+/// 物品ID小于该值的素材是偏属性水晶，不计入材料清单
+const CRYSTAL_ID_END: u32 = 20;
+
+/// 制作某个物品所使用的配方
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BomRecipe {
+    pub recipe_id: u32,
+    pub job: String,
+    /// 每次制作得到的成品数量
+    pub item_amount: u32,
+    /// 每次制作消耗的素材，`(item_id, amount)`
+    pub ingredients: Vec<(u32, u32)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BomItem {
+    pub name: String,
+    /// 无法制作的物品为`None`
+    pub recipe: Option<BomRecipe>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BomNode {
+    pub item_id: u32,
+    pub name: String,
+    pub amount: u32,
+    pub job: Option<String>,
+    /// 为得到`amount`个成品需要制作的次数
+    pub crafts: u32,
+    pub ingredients: Vec<BomNode>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BomMaterial {
+    pub item_id: u32,
+    pub name: String,
+    pub amount: u32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BomCraft {
+    pub item_id: u32,
+    pub name: String,
+    pub recipe_id: u32,
+    pub job: String,
+    pub crafts: u32,
+    /// 需要的成品数量
+    pub amount: u32,
+    /// 按配方的产量制作后多出的成品数量
+    pub wasted: u32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Bom {
+    /// 每个目标物品的展开树，各分支分别按产量取整
+    pub tree: Vec<BomNode>,
+    /// 合并所有分支后需要准备的原材料
+    pub materials: Vec<BomMaterial>,
+    /// 按先制作半成品、后制作成品的顺序排列的制作步骤，同一物品的需求合并后再按产量取整
+    pub crafts: Vec<BomCraft>,
+}
+
+impl BomRecipe {
+    fn ingredients(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.ingredients
+            .iter()
+            .copied()
+            .filter(|&(id, _)| id >= CRYSTAL_ID_END)
+    }
+
+    fn crafts(&self, amount: u32) -> u32 {
+        amount.div_ceil(self.item_amount.max(1))
+    }
+}
+
+/// 从目标物品出发，沿配方递归地找出需要查询的所有物品。
+/// `fetch`查询一个物品的信息，后端在其中访问数据库
+pub async fn collect_items<F, Fut, E>(
+    targets: &[(u32, u32)],
+    mut fetch: F,
+) -> Result<HashMap<u32, BomItem>, E>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: std::future::Future<Output = Result<BomItem, E>>,
+{
+    let mut items = HashMap::new();
+    let mut queue: VecDeque<u32> = targets.iter().map(|&(id, _)| id).collect();
+    while let Some(item_id) = queue.pop_front() {
+        if items.contains_key(&item_id) {
+            continue;
+        }
+        let item = fetch(item_id).await?;
+        if let Some(recipe) = &item.recipe {
+            queue.extend(recipe.ingredients().map(|(id, _)| id));
+        }
+        items.insert(item_id, item);
+    }
+    Ok(items)
+}
+
+/// 计算制作目标物品所需的完整材料清单。
+/// `targets`为`(item_id, amount)`，`items`需要包含所有可能用到的物品，通常由[`collect_items`]得到
+pub fn resolve(targets: &[(u32, u32)], items: &HashMap<u32, BomItem>) -> Result<Bom, String> {
+    let get = |id: u32| items.get(&id).ok_or_else(|| format!("unknown-item: {id}"));
+
+    // 对所有物品拓扑排序，保证每个物品都排在它的所有素材之前
+    // 使用BTreeMap使得没有依赖关系的物品之间顺序固定
+    let mut indegrees: BTreeMap<u32, usize> = items.keys().map(|&id| (id, 0)).collect();
+    for item in items.values() {
+        for (id, _) in item.recipe.iter().flat_map(BomRecipe::ingredients) {
+            *indegrees.entry(id).or_default() += 1;
+        }
+    }
+    let mut queue: VecDeque<u32> = indegrees
+        .iter()
+        .filter(|(_, &d)| d == 0)
+        .map(|(&id, _)| id)
+        .collect();
+    let mut sorted = Vec::with_capacity(indegrees.len());
+    while let Some(id) = queue.pop_front() {
+        sorted.push(id);
+        for (ing, _) in get(id)?.recipe.iter().flat_map(BomRecipe::ingredients) {
+            let d = indegrees.get_mut(&ing).unwrap();
+            *d -= 1;
+            if *d == 0 {
+                queue.push_back(ing);
+            }
+        }
+    }
+    if sorted.len() != indegrees.len() {
+        return Err(String::from("cyclic-recipes"));
+    }
+
+    let mut required: HashMap<u32, u32> = HashMap::new();
+    for &(id, amount) in targets {
+        *required.entry(id).or_default() += amount;
+    }
+    let mut materials = BTreeMap::new();
+    let mut crafts = Vec::new();
+    for &id in &sorted {
+        let amount = required.get(&id).copied().unwrap_or(0);
+        if amount == 0 {
+            continue;
+        }
+        let item = get(id)?;
+        let Some(recipe) = &item.recipe else {
+            materials.insert(id, amount);
+            continue;
+        };
+        let n = recipe.crafts(amount);
+        for (ing, ing_amount) in recipe.ingredients() {
+            *required.entry(ing).or_default() += n * ing_amount;
+        }
+        crafts.push(BomCraft {
+            item_id: id,
+            name: item.name.clone(),
+            recipe_id: recipe.recipe_id,
+            job: recipe.job.clone(),
+            crafts: n,
+            amount,
+            wasted: n * recipe.item_amount.max(1) - amount,
+        });
+    }
+    crafts.reverse();
+
+    let mut path = HashSet::new();
+    let tree = targets
+        .iter()
+        .map(|&(id, amount)| expand(id, amount, items, &mut path))
+        .collect::<Result<_, _>>()?;
+    let materials = materials
+        .into_iter()
+        .map(|(item_id, amount)| {
+            Ok(BomMaterial {
+                item_id,
+                name: get(item_id)?.name.clone(),
+                amount,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(Bom {
+        tree,
+        materials,
+        crafts,
+    })
+}
+
+fn expand(
+    item_id: u32,
+    amount: u32,
+    items: &HashMap<u32, BomItem>,
+    path: &mut HashSet<u32>,
+) -> Result<BomNode, String> {
+    let item = items
+        .get(&item_id)
+        .ok_or_else(|| format!("unknown-item: {item_id}"))?;
+    let mut node = BomNode {
+        item_id,
+        name: item.name.clone(),
+        amount,
+        job: None,
+        crafts: 0,
+        ingredients: Vec::new(),
+    };
+    if let Some(recipe) = &item.recipe {
+        if !path.insert(item_id) {
+            return Err(String::from("cyclic-recipes"));
+        }
+        node.job = Some(recipe.job.clone());
+        node.crafts = recipe.crafts(amount);
+        node.ingredients = recipe
+            .ingredients()
+            .map(|(id, n)| expand(id, node.crafts * n, items, path))
+            .collect::<Result<_, _>>()?;
+        path.remove(&item_id);
+    }
+    Ok(node)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{resolve, BomItem, BomRecipe};
+
+    fn item(name: &str) -> BomItem {
+        BomItem {
+            name: name.to_string(),
+            recipe: None,
+        }
+    }
+
+    fn craft(name: &str, recipe_id: u32, item_amount: u32, ingredients: &[(u32, u32)]) -> BomItem {
+        BomItem {
+            recipe: Some(BomRecipe {
+                recipe_id,
+                job: String::from("Carpenter"),
+                item_amount,
+                ingredients: ingredients.to_vec(),
+            }),
+            ..item(name)
+        }
+    }
+
+    #[test]
+    fn shared_intermediates_are_merged() {
+        // 两个成品都用到产量为3的半成品100，半成品需要水晶2和原材料200
+        let items = HashMap::from([
+            (1000, craft("A", 1, 1, &[(100, 2), (2, 1)])),
+            (1001, craft("B", 2, 1, &[(100, 2), (200, 1)])),
+            (100, craft("Board", 3, 3, &[(200, 2), (2, 1)])),
+            (200, item("Log")),
+        ]);
+        let bom = resolve(&[(1000, 1), (1001, 1)], &items).unwrap();
+
+        let order: Vec<_> = bom.crafts.iter().map(|c| c.item_id).collect();
+        assert_eq!(order.len(), 3);
+        assert_eq!(order[0], 100);
+        let board = &bom.crafts[0];
+        assert_eq!((board.amount, board.crafts, board.wasted), (4, 2, 2));
+        assert_eq!(bom.materials.len(), 1);
+        assert_eq!(bom.materials[0].amount, 2 * 2 + 1);
+        // 树中的每个分支分别取整
+        assert_eq!(bom.tree[0].ingredients[0].crafts, 1);
+    }
+
+    #[test]
+    fn craft_order_is_deterministic() {
+        let items = HashMap::from([
+            (1000, craft("A", 1, 1, &[(100, 1)])),
+            (1001, craft("B", 2, 1, &[(101, 1)])),
+            (100, craft("Board", 3, 1, &[(200, 1)])),
+            (101, craft("Lumber", 4, 1, &[(200, 1)])),
+            (200, item("Log")),
+        ]);
+        let bom = resolve(&[(1001, 1), (1000, 1)], &items).unwrap();
+        let order: Vec<_> = bom.crafts.iter().map(|c| c.item_id).collect();
+        assert_eq!(order, [101, 100, 1001, 1000]);
+    }
+}
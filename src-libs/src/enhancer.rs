@@ -0,0 +1,120 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ffxiv_crafting::Attributes;
+use serde::{Deserialize, Serialize};
+
+/// 食物或药水，各项加成以百分比和上限表示
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Enhancer {
+    pub name: String,
+    pub level: u32,
+    pub is_hq: bool,
+
+    pub cm: Option<i32>,
+    pub cm_max: Option<i32>,
+    pub ct: Option<i32>,
+    pub ct_max: Option<i32>,
+    pub cp: Option<i32>,
+    pub cp_max: Option<i32>,
+}
+
+/// 食物和药水提供的属性加成
+#[derive(Default, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bonus {
+    pub cm: i32,
+    pub ct: i32,
+    pub cp: i32,
+}
+
+impl Enhancer {
+    /// 计算对基础属性提供的加成，按百分比计算后向下取整，且不超过上限
+    pub fn bonus(&self, attributes: &Attributes) -> Bonus {
+        fn calc(base: i32, percent: Option<i32>, max: Option<i32>) -> i32 {
+            match (percent, max) {
+                (Some(percent), Some(max)) => (base * percent / 100).min(max),
+                _ => 0,
+            }
+        }
+        Bonus {
+            cm: calc(attributes.craftsmanship, self.cm, self.cm_max),
+            ct: calc(attributes.control, self.ct, self.ct_max),
+            cp: calc(attributes.craft_points, self.cp, self.cp_max),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnhancedAttributes {
+    pub attributes: Attributes,
+    pub bonus: Bonus,
+}
+
+/// 将任意组合的食物和药水应用到基础属性上。
+/// 每一项加成都以基础属性为准分别计算再相加，不会互相叠乘
+pub fn apply_enhancers(attributes: Attributes, enhancers: &[Enhancer]) -> EnhancedAttributes {
+    let mut bonus = Bonus::default();
+    for b in enhancers.iter().map(|e| e.bonus(&attributes)) {
+        bonus.cm += b.cm;
+        bonus.ct += b.ct;
+        bonus.cp += b.cp;
+    }
+    EnhancedAttributes {
+        attributes: Attributes {
+            craftsmanship: attributes.craftsmanship + bonus.cm,
+            control: attributes.control + bonus.ct,
+            craft_points: attributes.craft_points + bonus.cp,
+            ..attributes
+        },
+        bonus,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::Attributes;
+
+    use super::{apply_enhancers, Enhancer};
+
+    #[test]
+    fn capped_and_floored() {
+        let attributes = Attributes {
+            level: 100,
+            craftsmanship: 4900,
+            control: 4800,
+            craft_points: 615,
+        };
+        let meal = Enhancer {
+            cm: Some(5),
+            cm_max: Some(97),
+            cp: Some(26),
+            cp_max: Some(86),
+            ..Default::default()
+        };
+        let medicine = Enhancer {
+            ct: Some(3),
+            ct_max: Some(151),
+            ..Default::default()
+        };
+        let result = apply_enhancers(attributes, &[meal, medicine]);
+        assert_eq!(result.bonus.cm, 97);
+        assert_eq!(result.bonus.ct, 144);
+        assert_eq!(result.bonus.cp, 86);
+        assert_eq!(result.attributes.control, 4944);
+        assert_eq!(result.attributes.level, 100);
+    }
+}
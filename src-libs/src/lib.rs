@@ -15,13 +15,21 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 pub mod analyzer;
+pub mod bom;
+pub mod enhancer;
+pub mod macros;
+pub mod recipe;
+pub mod rotation;
 pub mod solver;
+pub mod trace;
 
 pub use ffxiv_crafting;
 use ffxiv_crafting::{
     Actions, Attributes, CastActionError, Condition, ConditionIterator, Recipe, Status,
 };
-use rand::{seq::IndexedRandom, Rng};
+use rand::{seq::IndexedRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rotation::Rotation;
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -61,13 +69,66 @@ pub fn simulate(status: Status, actions: Vec<Actions>) -> SimulateResult {
     result
 }
 
+#[derive(Serialize)]
+pub struct SimulateRotationResult {
+    pub status: Status,
+    /// 实际按顺序释放的技能，`errors`中的位置即是这个列表中的下标
+    pub actions: Vec<Actions>,
+    pub errors: Vec<CastErrorPos>,
+}
+
+/// 与[`simulate`]相同，但技能由条件技能序列根据每一步的状态决定
+pub fn simulate_rotation(status: Status, rotation: &Rotation) -> SimulateRotationResult {
+    let mut result = SimulateRotationResult {
+        status,
+        actions: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut pc = 0;
+    while let Some(sk) = rotation.next_action(&mut pc, &result.status) {
+        match result.status.is_action_allowed(sk) {
+            Ok(_) => result.status.cast_action(sk),
+            Err(err) => result.errors.push(CastErrorPos {
+                pos: result.actions.len(),
+                err,
+            }),
+        }
+        result.actions.push(sk);
+    }
+    result
+}
+
+/// 模拟使用的随机数生成器。
+/// 固定使用ChaCha8算法，同一个种子总会产生相同的随机序列，不受`rand`版本升级的影响
+pub type SimulationRng = ChaCha8Rng;
+
+/// 创建模拟使用的随机数生成器。
+/// 指定种子时可用于复现模拟结果，不指定种子时从系统随机源初始化
+pub fn new_rng(seed: Option<u64>) -> SimulationRng {
+    match seed {
+        Some(seed) => SimulationRng::seed_from_u64(seed),
+        None => SimulationRng::from_rng(&mut rand::rng()),
+    }
+}
+
+/// 创建模拟第`step`步时使用的随机数生成器。
+/// 逐步模拟时每一步都会重新创建生成器，以步数作为ChaCha的流编号，
+/// 避免每一步都重复同一个随机数，从而用同一个种子复现完整的制作过程。
+/// 不同种子的各步之间不会互相重复
+pub fn new_step_rng(seed: Option<u64>, step: i32) -> SimulationRng {
+    let mut rng = new_rng(seed);
+    rng.set_stream(step as u64);
+    rng
+}
+
 #[derive(Serialize)]
 pub struct SimulateOneStepResult {
     pub status: Status,
     pub is_success: bool,
 }
 
-/// 只模拟一步制作，计算技能概率和制作状态，更新制作状态
+/// 只模拟一步制作，计算技能概率和制作状态，更新制作状态。
+/// 技能成功与否以及下一回合的球色都由`rng`决定，传入[`new_rng`]创建的生成器即可复现结果
 pub fn simulate_one_step(
     status: &mut Status,
     action: Actions,
@@ -87,12 +148,10 @@ pub fn simulate_one_step(
         });
     }
     if !matches!(action, Actions::FinalAppraisal | Actions::HeartAndSoul) {
-        status.condition = match status.condition {
-            Condition::Good if !force_success => Condition::Normal,
-            Condition::Excellent if !force_success => Condition::Poor,
-            Condition::Poor => Condition::Normal,
-            Condition::GoodOmen => Condition::Good,
-            _ => {
+        // 需要随机决定时总是从随机数生成器中抽取，以保持相同种子产生的随机序列不变
+        status.condition = match fixed_next_condition(status, force_success) {
+            Some(condition) => condition,
+            None => {
                 ConditionIterator::new(
                     status.recipe.conditions_flag as i32,
                     status.attributes.level as i32,
@@ -107,6 +166,90 @@ pub fn simulate_one_step(
     Ok(is_success)
 }
 
+/// 计算下一回合可能出现的球色及其概率，概率之和为1。
+/// 释放最终确认和专心致志不会进入下一回合，调用前需要自行排除
+pub(crate) fn next_conditions(status: &Status, force_success: bool) -> Vec<(Condition, f64)> {
+    if let Some(condition) = fixed_next_condition(status, force_success) {
+        return vec![(condition, 1.0)];
+    }
+    let conditions: Vec<_> = ConditionIterator::new(
+        status.recipe.conditions_flag as i32,
+        status.attributes.level as i32,
+    )
+    .map(|(c, w)| (c, w as f64))
+    .collect();
+    let total: f64 = conditions.iter().map(|c| c.1).sum();
+    conditions
+        .into_iter()
+        .map(|(c, w)| (c, w / total))
+        .collect()
+}
+
+/// 由当前球色唯一决定的下一回合球色，需要随机决定时返回`None`
+fn fixed_next_condition(status: &Status, force_success: bool) -> Option<Condition> {
+    match status.condition {
+        Condition::Good if !force_success => Some(Condition::Normal),
+        Condition::Excellent if !force_success => Some(Condition::Poor),
+        Condition::Poor => Some(Condition::Normal),
+        Condition::GoodOmen => Some(Condition::Good),
+        _ => None,
+    }
+}
+
+#[derive(Serialize)]
+pub struct SimulateRotationOneStepResult {
+    pub status: Status,
+    /// 这一步释放的技能，技能序列已经结束时为`None`
+    pub action: Option<Actions>,
+    pub is_success: bool,
+    /// 下一步在技能序列中开始执行的位置
+    pub pc: usize,
+    /// 技能不满足释放条件时的错误，此时状态不变，但`pc`仍然指向下一个技能
+    pub error: Option<CastActionError>,
+}
+
+/// 从条件技能序列的`pc`处取出当前状态下应当释放的技能，并按[`simulate_one_step`]模拟这一步。
+/// 技能序列已经结束时返回`Ok(None)`，否则返回释放的技能及其是否成功
+pub fn simulate_rotation_one_step(
+    status: &mut Status,
+    rotation: &Rotation,
+    pc: &mut usize,
+    force_success: bool,
+    rng: &mut impl Rng,
+) -> Result<Option<(Actions, bool)>, CastActionError> {
+    let Some(action) = rotation.next_action(pc, status) else {
+        return Ok(None);
+    };
+    simulate_one_step(status, action, force_success, rng)
+        .map(|is_success| Some((action, is_success)))
+}
+
+/// 与[`simulate_rotation_one_step`]相同，但技能无法释放时不返回错误，而是记录在结果中，
+/// 逐步模拟的调用方可以从返回的`pc`处跳过出错的技能继续执行
+pub fn simulate_rotation_step(
+    mut status: Status,
+    rotation: &Rotation,
+    mut pc: usize,
+    force_success: bool,
+    rng: &mut impl Rng,
+) -> SimulateRotationOneStepResult {
+    // 出错时状态不变，`action`就是出错的技能
+    let mut next = pc;
+    let action = rotation.next_action(&mut next, &status);
+    let (is_success, error) =
+        match simulate_rotation_one_step(&mut status, rotation, &mut pc, force_success, rng) {
+            Ok(step) => (step.is_some_and(|(_, is_success)| is_success), None),
+            Err(err) => (false, Some(err)),
+        };
+    SimulateRotationOneStepResult {
+        status,
+        action,
+        is_success,
+        pc,
+        error,
+    }
+}
+
 /// 计算当前状态下可以释放技能的集合，用于模拟界面将不可释放技能置灰
 pub fn allowed_list(status: Status, skills: Vec<Actions>) -> Vec<String> {
     skills
@@ -127,3 +270,42 @@ pub fn craftpoints_list(status: Status, skills: Vec<Actions>) -> Vec<i32> {
 pub fn high_quality_probability(status: Status) -> Option<i32> {
     status.high_quality_probability()
 }
+
+/// 测试中通用的制作状态：90级配方，作业精度4214、加工精度3528、制作力691
+#[cfg(test)]
+pub(crate) fn test_status() -> Status {
+    let recipe = Recipe {
+        rlv: ffxiv_crafting::data::recipe_level_table(620),
+        job_level: 90,
+        difficulty: 5720,
+        quality: 12900,
+        durability: 70,
+        conditions_flag: 15,
+    };
+    let attributes = Attributes {
+        level: 90,
+        craftsmanship: 4214,
+        control: 3528,
+        craft_points: 691,
+    };
+    Status::new(attributes, recipe)
+}
+
+#[cfg(test)]
+mod test {
+    use rand::Rng;
+
+    use super::new_step_rng;
+
+    #[test]
+    fn step_rng_streams_do_not_overlap() {
+        let draw = |seed, step| {
+            let mut rng = new_step_rng(Some(seed), step);
+            (0..8).map(|_| rng.random::<u64>()).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(7, 3), draw(7, 3));
+        assert_ne!(draw(7, 3), draw(7, 4));
+        // 相邻的种子不应当只是错开了一步
+        assert_ne!(draw(7, 4), draw(8, 3));
+    }
+}
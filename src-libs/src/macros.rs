@@ -0,0 +1,636 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! 游戏内宏的解析与生成
+
+use ffxiv_crafting::Actions;
+use serde::{Deserialize, Serialize};
+
+/// 一个宏最多能有的行数
+pub const MAX_LINES_PER_MACRO: usize = 15;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Zh,
+    #[default]
+    En,
+    Ja,
+    De,
+    Fr,
+}
+
+impl Language {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn finished_message(self, id: usize) -> String {
+        match self {
+            Language::Zh => format!("宏#{id} 已完成！"),
+            Language::En => format!("M#{id} is finished!"),
+            Language::Ja => format!("マクロ#{id} 完了！"),
+            Language::De => format!("Makro #{id} ist fertig!"),
+            Language::Fr => format!("Macro #{id} terminée !"),
+        }
+    }
+}
+
+/// 技能在各语言客户端中的名称，顺序与[`Language`]相同
+const ACTION_NAMES: [(Actions, [&str; 5]); 36] = [
+    (
+        Actions::BasicSynthesis,
+        [
+            "制作",
+            "Basic Synthesis",
+            "作業",
+            "Bearbeiten",
+            "Travail de base",
+        ],
+    ),
+    (
+        Actions::BasicTouch,
+        [
+            "加工",
+            "Basic Touch",
+            "加工",
+            "Veredelung",
+            "Ouvrage de base",
+        ],
+    ),
+    (
+        Actions::MastersMend,
+        [
+            "精修",
+            "Master's Mend",
+            "マスターズメンド",
+            "Wiederherstellung",
+            "Réparation de maître",
+        ],
+    ),
+    (
+        Actions::HastyTouch,
+        [
+            "仓促",
+            "Hasty Touch",
+            "ヘイスティタッチ",
+            "Hastige Veredelung",
+            "Ouvrage hâtif",
+        ],
+    ),
+    (
+        Actions::RapidSynthesis,
+        [
+            "高速制作",
+            "Rapid Synthesis",
+            "突貫作業",
+            "Schnelle Bearbeitung",
+            "Travail preste",
+        ],
+    ),
+    (
+        Actions::Observe,
+        ["观察", "Observe", "経過観察", "Beobachten", "Observation"],
+    ),
+    (
+        Actions::TricksOfTheTrade,
+        [
+            "秘诀",
+            "Tricks of the Trade",
+            "秘訣",
+            "Kunstgriff",
+            "Ficelles du métier",
+        ],
+    ),
+    (
+        Actions::WasteNot,
+        ["俭约", "Waste Not", "倹約", "Nachhaltigkeit", "Parcimonie"],
+    ),
+    (
+        Actions::Veneration,
+        [
+            "崇敬",
+            "Veneration",
+            "ヴェネレーション",
+            "Ehrfurcht",
+            "Vénération",
+        ],
+    ),
+    (
+        Actions::StandardTouch,
+        [
+            "中级加工",
+            "Standard Touch",
+            "中級加工",
+            "Solide Veredelung",
+            "Ouvrage standard",
+        ],
+    ),
+    (
+        Actions::GreatStrides,
+        [
+            "阔步",
+            "Great Strides",
+            "グレートストライド",
+            "Große Schritte",
+            "Grands progrès",
+        ],
+    ),
+    (
+        Actions::Innovation,
+        [
+            "改革",
+            "Innovation",
+            "イノベーション",
+            "Innovation",
+            "Innovation",
+        ],
+    ),
+    (
+        Actions::FinalAppraisal,
+        [
+            "最终确认",
+            "Final Appraisal",
+            "最終確認",
+            "Schlussbewertung",
+            "Dernière révision",
+        ],
+    ),
+    (
+        Actions::WasteNotII,
+        [
+            "长期俭约",
+            "Waste Not II",
+            "長期倹約",
+            "Nachhaltigkeit II",
+            "Parcimonie pérenne",
+        ],
+    ),
+    (
+        Actions::ByregotsBlessing,
+        [
+            "比尔格的祝福",
+            "Byregot's Blessing",
+            "ビエルゴの祝福",
+            "Byregots Benediktion",
+            "Bénédiction de Byregot",
+        ],
+    ),
+    (
+        Actions::PreciseTouch,
+        [
+            "集中加工",
+            "Precise Touch",
+            "集中加工",
+            "Präzise Veredelung",
+            "Ouvrage précis",
+        ],
+    ),
+    (
+        Actions::MuscleMemory,
+        [
+            "坚信",
+            "Muscle Memory",
+            "確信",
+            "Motorisches Gedächtnis",
+            "Mémoire musculaire",
+        ],
+    ),
+    (
+        Actions::CarefulSynthesis,
+        [
+            "模范制作",
+            "Careful Synthesis",
+            "模範作業",
+            "Sorgfältige Bearbeitung",
+            "Travail prudent",
+        ],
+    ),
+    (
+        Actions::Manipulation,
+        [
+            "掌握",
+            "Manipulation",
+            "マニピュレーション",
+            "Manipulation",
+            "Manipulation",
+        ],
+    ),
+    (
+        Actions::PrudentTouch,
+        [
+            "俭约加工",
+            "Prudent Touch",
+            "倹約加工",
+            "Nachhaltige Veredelung",
+            "Ouvrage parcimonieux",
+        ],
+    ),
+    (
+        Actions::Reflect,
+        ["闲静", "Reflect", "真価", "Einkehr", "Réflexion"],
+    ),
+    (
+        Actions::PreparatoryTouch,
+        [
+            "坯料加工",
+            "Preparatory Touch",
+            "下地加工",
+            "Basisveredelung",
+            "Ouvrage préparatoire",
+        ],
+    ),
+    (
+        Actions::Groundwork,
+        [
+            "坯料制作",
+            "Groundwork",
+            "下地作業",
+            "Vorarbeit",
+            "Travail préparatoire",
+        ],
+    ),
+    (
+        Actions::DelicateSynthesis,
+        [
+            "精密制作",
+            "Delicate Synthesis",
+            "精密作業",
+            "Feine Bearbeitung",
+            "Travail minutieux",
+        ],
+    ),
+    (
+        Actions::IntensiveSynthesis,
+        [
+            "集中制作",
+            "Intensive Synthesis",
+            "集中作業",
+            "Fokussierte Bearbeitung",
+            "Travail vigilant",
+        ],
+    ),
+    (
+        Actions::TrainedEye,
+        [
+            "工匠的神速技巧",
+            "Trained Eye",
+            "匠の早業",
+            "Flinke Hand",
+            "Main preste",
+        ],
+    ),
+    (
+        Actions::AdvancedTouch,
+        [
+            "上级加工",
+            "Advanced Touch",
+            "上級加工",
+            "Höhere Veredelung",
+            "Ouvrage avancé",
+        ],
+    ),
+    (
+        Actions::PrudentSynthesis,
+        [
+            "俭约制作",
+            "Prudent Synthesis",
+            "倹約作業",
+            "Rationelle Bearbeitung",
+            "Travail économe",
+        ],
+    ),
+    (
+        Actions::TrainedFinesse,
+        [
+            "工匠的神技",
+            "Trained Finesse",
+            "匠の神業",
+            "Meisterliche Veredelung",
+            "Main experte",
+        ],
+    ),
+    (
+        Actions::CarefulObservation,
+        [
+            "设计变动",
+            "Careful Observation",
+            "設計変更",
+            "Genaue Beobachtung",
+            "Observation méticuleuse",
+        ],
+    ),
+    (
+        Actions::HeartAndSoul,
+        [
+            "专心致志",
+            "Heart and Soul",
+            "一心不乱",
+            "Mit Leib und Seele",
+            "Cœur et âme",
+        ],
+    ),
+    (
+        Actions::RefinedTouch,
+        [
+            "精炼加工",
+            "Refined Touch",
+            "洗練加工",
+            "Raffinierte Veredelung",
+            "Ouvrage raffiné",
+        ],
+    ),
+    (
+        Actions::DaringTouch,
+        [
+            "冒进",
+            "Daring Touch",
+            "デアリングタッチ",
+            "Gewagte Veredelung",
+            "Ouvrage audacieux",
+        ],
+    ),
+    (
+        Actions::ImmaculateMend,
+        [
+            "巧夺天工",
+            "Immaculate Mend",
+            "パーフェクトメンド",
+            "Makellose Wiederherstellung",
+            "Réparation immaculée",
+        ],
+    ),
+    (
+        Actions::QuickInnovation,
+        [
+            "快速改革",
+            "Quick Innovation",
+            "クイックイノベーション",
+            "Schnelle Innovation",
+            "Innovation instantanée",
+        ],
+    ),
+    (
+        Actions::TrainedPerfection,
+        [
+            "工匠的绝技",
+            "Trained Perfection",
+            "匠の絶技",
+            "Meisterliche Perfektion",
+            "Perfection experte",
+        ],
+    ),
+];
+
+/// 宏中表示释放技能的指令
+const ACTION_COMMANDS: [&str; 4] = ["ac", "action", "技能", "aktion"];
+
+/// 获取技能在指定语言客户端中的名称，失败的技能按原技能处理
+pub fn action_name(action: Actions, lang: Language) -> &'static str {
+    let action = match action {
+        Actions::RapidSynthesisFail => Actions::RapidSynthesis,
+        Actions::HastyTouchFail => Actions::HastyTouch,
+        Actions::DaringTouchFail => Actions::DaringTouch,
+        action => action,
+    };
+    ACTION_NAMES
+        .iter()
+        .find(|(a, _)| *a == action)
+        .map(|(_, names)| names[lang.index()])
+        .unwrap()
+}
+
+/// 通过任意语言的技能名称查找技能，不区分大小写
+pub fn find_action(name: &str) -> Option<Actions> {
+    let name = name.to_lowercase();
+    ACTION_NAMES
+        .iter()
+        .find(|(_, names)| names.iter().any(|n| n.to_lowercase() == name))
+        .map(|(a, _)| *a)
+}
+
+/// 技能释放后到可以释放下一个技能所需等待的秒数
+pub fn wait_time(action: Actions) -> f32 {
+    match action {
+        Actions::WasteNot
+        | Actions::Veneration
+        | Actions::GreatStrides
+        | Actions::Innovation
+        | Actions::FinalAppraisal
+        | Actions::WasteNotII
+        | Actions::Manipulation => 1.07,
+        _ => 2.17,
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct UnknownLine {
+    /// 从1开始的行号
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct ParseResult {
+    pub actions: Vec<Actions>,
+    pub unknown_lines: Vec<UnknownLine>,
+}
+
+/// 解析游戏内的宏文本，支持中、英、日、德、法五种语言的技能名称。
+///
+/// 技能指令后的名称可以带引号也可以不带，`<wait.N>`等标签会被忽略；
+/// 不释放技能的其他指令（如`/mlock`、`/echo`）会被跳过，
+/// 无法识别技能名称的行则记录在`unknown_lines`中
+pub fn parse(text: &str) -> ParseResult {
+    let mut result = ParseResult::default();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let body = match line.strip_prefix('/') {
+            Some(command) => {
+                let (cmd, body) = command
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((command, ""));
+                if !ACTION_COMMANDS.contains(&cmd.to_lowercase().as_str()) {
+                    continue;
+                }
+                body
+            }
+            None => line,
+        };
+        match find_action(strip_tags(body).trim_matches('"')) {
+            Some(action) => result.actions.push(action),
+            None => result.unknown_lines.push(UnknownLine {
+                line: i + 1,
+                text: line.to_string(),
+            }),
+        }
+    }
+    result
+}
+
+/// 去掉指令中所有`<...>`形式的标签
+fn strip_tags(body: &str) -> &str {
+    let mut body = body.trim();
+    while let Some(rest) = body.strip_suffix('>') {
+        match rest.rfind('<') {
+            Some(start) => body = rest[..start].trim_end(),
+            None => break,
+        }
+    }
+    body
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Notification {
+    /// 只在不会因此增加宏的数量时添加完成提示
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GenerateOptions {
+    pub language: Language,
+    /// 在每个技能的默认等待时间上额外增加的秒数
+    pub wait_time_inc: u32,
+    /// 是否在每个宏的开头添加`/mlock`
+    pub lock: bool,
+    /// 是否把技能平均分配到每个宏中，否则尽量填满前面的宏
+    pub avg_size: bool,
+    /// 是否在每个宏的末尾添加`/echo`完成提示
+    pub notification: Notification,
+    /// 完成提示的音效编号，对应`<se.N>`
+    pub notify_sound: Option<u8>,
+}
+
+/// 将技能序列生成为游戏内的宏，每个宏不超过15行。
+/// 返回值中每个元素是一个宏的全部行
+pub fn generate(actions: &[Actions], options: &GenerateOptions) -> Vec<Vec<String>> {
+    if actions.is_empty() {
+        return Vec::new();
+    }
+    let mut max_lines = MAX_LINES_PER_MACRO;
+    if options.lock {
+        max_lines -= 1;
+    }
+    let notify = match options.notification {
+        Notification::Always => true,
+        Notification::Never => false,
+        Notification::Auto => {
+            actions.len().div_ceil(max_lines) == actions.len().div_ceil(max_lines - 1)
+        }
+    };
+    if notify {
+        max_lines -= 1;
+    }
+    let chunk_size = if options.avg_size {
+        actions.len().div_ceil(actions.len().div_ceil(max_lines))
+    } else {
+        max_lines
+    };
+
+    let mut macros = Vec::new();
+    for (i, chunk) in actions.chunks(chunk_size).enumerate() {
+        let mut lines = Vec::with_capacity(MAX_LINES_PER_MACRO);
+        if options.lock {
+            lines.push(String::from("/mlock"));
+        }
+        for &action in chunk {
+            // 宏中无法使用冒进，用仓促代替
+            let action = match action {
+                Actions::DaringTouch => Actions::HastyTouch,
+                action => action,
+            };
+            let name = action_name(action, options.language);
+            let wait = wait_time(action).ceil() as u32 + options.wait_time_inc;
+            if name.contains(' ') {
+                lines.push(format!("/ac \"{name}\" <wait.{wait}>"));
+            } else {
+                lines.push(format!("/ac {name} <wait.{wait}>"));
+            }
+        }
+        if notify {
+            let message = options.language.finished_message(i + 1);
+            match options.notify_sound {
+                Some(se) => lines.push(format!("/e {message} <se.{se}>")),
+                None => lines.push(format!("/e {message}")),
+            }
+        }
+        macros.push(lines);
+    }
+    macros
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_mixed_languages() {
+        let text = "/mlock\n\
+            /ac \"Basic Synthesis\" <wait.3>\n\
+            /ac 制作 <wait.3>\n\
+            /ac 経過観察\n\
+            /ac \"Große Schritte\" <wait.2>\n\
+            /action \"Ouvrage hâtif\" <wait.3>\n\
+            /ac \"Not An Action\" <wait.3>\n\
+            /e done <se.1>";
+        let result = parse(text);
+        assert_eq!(
+            result.actions,
+            vec![
+                Actions::BasicSynthesis,
+                Actions::BasicSynthesis,
+                Actions::Observe,
+                Actions::GreatStrides,
+                Actions::HastyTouch,
+            ]
+        );
+        assert_eq!(
+            result.unknown_lines,
+            vec![UnknownLine {
+                line: 7,
+                text: String::from("/ac \"Not An Action\" <wait.3>"),
+            }]
+        );
+    }
+
+    #[test]
+    fn generate_chunks() {
+        let actions = vec![Actions::BasicTouch; 20];
+        let options = GenerateOptions {
+            lock: true,
+            notification: Notification::Always,
+            notify_sound: Some(1),
+            ..Default::default()
+        };
+        let macros = generate(&actions, &options);
+        assert_eq!(macros.len(), 2);
+        assert!(macros.iter().all(|m| m.len() <= MAX_LINES_PER_MACRO));
+        assert_eq!(macros[0][0], "/mlock");
+        assert_eq!(macros[0][1], "/ac \"Basic Touch\" <wait.3>");
+        assert_eq!(macros[1].last().unwrap(), "/e M#2 is finished! <se.1>");
+
+        let lines: Vec<String> = macros.concat();
+        assert_eq!(parse(&lines.join("\n")).actions, actions);
+    }
+}
@@ -0,0 +1,302 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ffxiv_crafting::{Actions, Recipe, RecipeLevel, Status};
+use serde::{Deserialize, Serialize};
+
+/// 由配方等级表和配方中的难度、品质、耐久系数计算出模拟制作所需的配方信息。
+/// 各项系数以百分比表示，计算结果向下取整
+pub fn new_recipe(
+    rlv: RecipeLevel,
+    difficulty_factor: u16,
+    quality_factor: u16,
+    durability_factor: u16,
+) -> Recipe {
+    let apply = |base: u32, factor: u16| base * factor as u32 / 100;
+    Recipe {
+        job_level: rlv.class_job_level,
+        difficulty: apply(rlv.difficulty as u32, difficulty_factor) as _,
+        quality: apply(rlv.quality, quality_factor) as _,
+        durability: apply(rlv.durability as u32, durability_factor) as _,
+        conditions_flag: rlv.conditions_flag,
+        rlv,
+    }
+}
+
+/// 配方中的一种素材及其中使用的HQ素材数量
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ingredient {
+    pub item_level: u32,
+    pub can_be_hq: bool,
+    pub amount: u32,
+    #[serde(default)]
+    pub hq_amount: u32,
+}
+
+/// 计算使用HQ素材时的初期品质。
+///
+/// 所有能够HQ的素材按物品品级加权，HQ素材所占的比例乘以配方的`material_quality_factor`（百分比）
+/// 即为初期品质占配方最高品质的比例，结果向下取整
+pub fn initial_quality(
+    recipe: &Recipe,
+    material_quality_factor: u32,
+    ingredients: &[Ingredient],
+) -> u32 {
+    let (total, hq) = ingredients
+        .iter()
+        .filter(|i| i.can_be_hq)
+        .fold((0, 0), |(total, hq), i| {
+            let hq_amount = i.hq_amount.min(i.amount);
+            (
+                total + (i.amount * i.item_level) as u64,
+                hq + (hq_amount * i.item_level) as u64,
+            )
+        });
+    if total == 0 {
+        return 0;
+    }
+    (recipe.quality as u64 * material_quality_factor as u64 * hq / (100 * total)) as u32
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct HqIngredientsPlan {
+    /// 与传入的素材一一对应的HQ素材数量
+    pub hq_amounts: Vec<u32>,
+    pub initial_quality: u32,
+}
+
+/// 计算以指定初始状态执行技能序列时，至少需要多少HQ素材才能推满品质。
+///
+/// 每个HQ素材提供的初期品质与其品级成正比，因此优先使用品级最高的素材能使HQ素材的总数最少。
+/// 不需要HQ素材时各项数量均为0，全部使用HQ素材也无法推满品质或推满进展时返回`None`。
+/// 传入素材中的`hq_amount`会被忽略
+pub fn required_hq_ingredients(
+    status: &Status,
+    actions: &[Actions],
+    material_quality_factor: u32,
+    ingredients: &[Ingredient],
+) -> Option<HqIngredientsPlan> {
+    let mut order: Vec<usize> = (0..ingredients.len())
+        .filter(|&i| ingredients[i].can_be_hq)
+        .collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(ingredients[i].item_level));
+
+    let mut next = order
+        .into_iter()
+        .flat_map(|i| std::iter::repeat_n(i, ingredients[i].amount as usize));
+    let mut current = ingredients.to_vec();
+    current.iter_mut().for_each(|i| i.hq_amount = 0);
+    loop {
+        let initial_quality = initial_quality(&status.recipe, material_quality_factor, &current);
+        let mut s = status.clone();
+        s.quality = initial_quality;
+        let s = crate::simulate(s, actions.to_vec()).status;
+        if s.progress >= s.recipe.difficulty && s.quality >= s.recipe.quality {
+            return Some(HqIngredientsPlan {
+                hq_amounts: current.iter().map(|i| i.hq_amount).collect(),
+                initial_quality,
+            });
+        }
+        current[next.next()?].hq_amount += 1;
+    }
+}
+
+/// 普通配方的球色标志，即只会出现通常、高品质、最高品质和低品质四种球色。
+///
+/// 游戏中的高难度配方（配方表中`IsExpert`为真的配方）都使用带有安定、结实等球色的配方等级，
+/// 例如`1011`，因此可以用球色标志是否等于该值来区分高难度配方
+pub const NORMAL_CONDITIONS_FLAG: u16 = 15;
+
+/// 按配方等级的球色标志判断是否为高难度配方
+pub fn is_expert(conditions_flag: u16) -> bool {
+    conditions_flag != NORMAL_CONDITIONS_FLAG
+}
+
+/// 配方列表的排序方式
+#[derive(Deserialize, Default, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RecipeOrder {
+    #[default]
+    Id,
+    /// 按制作等级和配方等级排序
+    Level,
+    /// 按乘以系数后的难度排序
+    Difficulty,
+    /// 按乘以系数后的最高品质排序
+    Quality,
+}
+
+/// 配方列表的附加筛选条件和排序方式，未指定的条件不做筛选。
+///
+/// `T`为各后端数据库中数值列的类型，具体的查询由后端根据自己的表结构生成
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct RecipeFilter<T> {
+    pub can_hq: Option<bool>,
+    pub collectable: Option<bool>,
+    /// 见[`is_expert`]
+    pub expert: Option<bool>,
+    pub craftsmanship_min: Option<T>,
+    pub craftsmanship_max: Option<T>,
+    pub control_min: Option<T>,
+    pub control_max: Option<T>,
+    /// 乘以系数后的耐久
+    pub durability_min: Option<T>,
+    pub durability_max: Option<T>,
+    pub order_by: RecipeOrder,
+    pub descending: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::RecipeLevel;
+
+    use ffxiv_crafting::Actions;
+
+    use super::{initial_quality, is_expert, new_recipe, required_hq_ingredients, Ingredient};
+    use crate::test_status;
+
+    fn rlv() -> RecipeLevel {
+        RecipeLevel {
+            class_job_level: 100,
+            stars: 0,
+            suggested_craftsmanship: 4900,
+            suggested_control: None,
+            difficulty: 7500,
+            quality: 16500,
+            progress_divider: 170,
+            quality_divider: 150,
+            progress_modifier: 90,
+            quality_modifier: 75,
+            durability: 80,
+            conditions_flag: 15,
+        }
+    }
+
+    fn ingredient(item_level: u32, can_be_hq: bool, amount: u32, hq_amount: u32) -> Ingredient {
+        Ingredient {
+            item_level,
+            can_be_hq,
+            amount,
+            hq_amount,
+        }
+    }
+
+    #[test]
+    fn expert_conditions_flags() {
+        // 通常、高品质、最高品质、低品质
+        assert!(!is_expert(1 | 2 | 4 | 8));
+        // 高难度配方：通常、高品质、安定、结实、高效、长持、坚实、好兆头
+        let expert = 1 | 2 | 16 | 32 | 64 | 128 | 256 | 512;
+        assert_eq!(expert, 1011);
+        assert!(is_expert(expert));
+    }
+
+    #[test]
+    fn factors_are_floored() {
+        let recipe = new_recipe(rlv(), 75, 103, 50);
+        assert_eq!(recipe.job_level, 100);
+        assert_eq!(recipe.difficulty, 5625);
+        assert_eq!(recipe.quality, 16995);
+        assert_eq!(recipe.durability, 40);
+        assert_eq!(recipe.conditions_flag, 15);
+    }
+
+    #[test]
+    fn initial_quality_weighted_by_item_level() {
+        let recipe = new_recipe(rlv(), 75, 103, 50);
+        let ingredients = [
+            ingredient(710, true, 2, 1),
+            ingredient(690, true, 3, 3),
+            ingredient(1, false, 5, 0),
+        ];
+        // 16995 * 75% * (710 + 690 * 3) / (710 * 2 + 690 * 3)
+        assert_eq!(initial_quality(&recipe, 75, &ingredients), 10153);
+        assert_eq!(initial_quality(&recipe, 75, &ingredients[2..]), 0);
+    }
+
+    /// 进展推满时加工获得的品质与初期品质无关
+    const ACTIONS: [Actions; 9] = [
+        Actions::MuscleMemory,
+        Actions::Manipulation,
+        Actions::Innovation,
+        Actions::PreparatoryTouch,
+        Actions::PreparatoryTouch,
+        Actions::GreatStrides,
+        Actions::ByregotsBlessing,
+        Actions::Groundwork,
+        Actions::CarefulSynthesis,
+    ];
+
+    #[test]
+    fn required_hq_ingredients_prefers_high_item_level() {
+        let mut status = test_status();
+        status.recipe.difficulty = 1500;
+        let gained = crate::simulate(status.clone(), ACTIONS.to_vec())
+            .status
+            .quality;
+        // 素材的品级合计为1700，最多提供一半的品质
+        let ingredients = [
+            ingredient(500, true, 2, 2),
+            ingredient(700, true, 1, 0),
+            ingredient(1, false, 3, 0),
+        ];
+
+        // 还需要28%的品质：只用品级700的素材提供约20.6%，再加一个品级500的素材提供约35.3%
+        status.recipe.quality = gained * 100 / 72;
+        let plan = required_hq_ingredients(&status, &ACTIONS, 50, &ingredients).unwrap();
+        assert_eq!(plan.hq_amounts, [1, 1, 0]);
+        let chosen = [
+            ingredient(500, true, 2, 1),
+            ingredient(700, true, 1, 1),
+            ingredient(1, false, 3, 0),
+        ];
+        assert_eq!(
+            plan.initial_quality,
+            initial_quality(&status.recipe, 50, &chosen)
+        );
+
+        // 品质已经足够时不需要HQ素材
+        status.recipe.quality = gained;
+        let plan = required_hq_ingredients(&status, &ACTIONS, 50, &ingredients).unwrap();
+        assert_eq!(plan.hq_amounts, [0, 0, 0]);
+    }
+
+    #[test]
+    fn required_hq_ingredients_unreachable() {
+        let mut status = test_status();
+        status.recipe.difficulty = 1500;
+        let gained = crate::simulate(status.clone(), ACTIONS.to_vec())
+            .status
+            .quality;
+        let ingredients = [ingredient(500, true, 2, 0), ingredient(700, true, 1, 0)];
+
+        // 还需要60%的品质，全部使用HQ素材也只有50%
+        let mut short = status.clone();
+        short.recipe.quality = gained * 100 / 40;
+        assert_eq!(
+            required_hq_ingredients(&short, &ACTIONS, 50, &ingredients),
+            None
+        );
+
+        // 没有推满进展
+        status.recipe.quality = gained;
+        assert_eq!(
+            required_hq_ingredients(&status, &ACTIONS[..7], 50, &ingredients),
+            None
+        );
+    }
+}
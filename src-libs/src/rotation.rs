@@ -0,0 +1,538 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! 带条件分支的技能序列
+//!
+//! 每行一个技能或一条控制语句，`#`之后的内容为注释：
+//!
+//! ```text
+//! muscle_memory
+//! manipulation
+//! if condition == good or condition == excellent
+//!     precise_touch
+//! elif great_strides and cp >= 32
+//!     "Prudent Touch"
+//! else
+//!     basic_touch
+//! end
+//! ```
+//!
+//! 技能可以写作JSON中使用的名称，也可以写作任意语言客户端中的名称（带空格时需加引号）。
+//! 条件中可以使用`condition`、`progress`、`quality`、`durability`、`cp`、`step`、
+//! `inner_quiet`、`progress_left`、`quality_left`以及各个增益的名称（剩余回合数），
+//! 支持比较运算`== != < <= > >=`、逻辑运算`and or not`和括号。
+//! 单独写出的变量表示它不为零。
+
+use std::{fmt, str::FromStr};
+
+use ffxiv_crafting::{Actions, Condition, LimitedActionState, Status};
+use serde::{de::IntoDeserializer, Deserialize};
+
+use crate::{macros::find_action, trace::BuffKind};
+
+/// 编译后的条件技能序列
+#[derive(Debug, Clone)]
+pub struct Rotation {
+    instructions: Vec<Instruction>,
+}
+
+#[derive(Debug, Clone)]
+enum Instruction {
+    Cast(Actions),
+    /// 条件不成立时跳转
+    JumpUnless(Expr, usize),
+    Jump(usize),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Var, Op, i64),
+    Condition(Op, Condition),
+    NonZero(Var),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Var {
+    Progress,
+    Quality,
+    Durability,
+    CraftPoints,
+    Step,
+    InnerQuiet,
+    ProgressLeft,
+    QualityLeft,
+    Buff(BuffKind),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 从1开始的行号
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Rotation {
+    /// 从`pc`处继续执行，返回按当前状态应当释放的下一个技能，序列结束时返回`None`
+    pub fn next_action(&self, pc: &mut usize, status: &Status) -> Option<Actions> {
+        // 只存在向后的跳转，因此一定会结束
+        while let Some(instruction) = self.instructions.get(*pc) {
+            match instruction {
+                Instruction::Cast(action) => {
+                    *pc += 1;
+                    return Some(*action);
+                }
+                Instruction::JumpUnless(expr, target) => {
+                    *pc = if expr.eval(status) { *pc + 1 } else { *target };
+                }
+                Instruction::Jump(target) => *pc = *target,
+            }
+        }
+        None
+    }
+}
+
+impl From<&[Actions]> for Rotation {
+    fn from(actions: &[Actions]) -> Self {
+        Self {
+            instructions: actions.iter().map(|a| Instruction::Cast(*a)).collect(),
+        }
+    }
+}
+
+struct Block {
+    /// 当前分支条件不成立时的跳转指令
+    pending: Option<usize>,
+    /// 各分支执行完毕后跳到`end`的指令
+    ends: Vec<usize>,
+    has_else: bool,
+}
+
+impl FromStr for Rotation {
+    type Err = ParseError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let mut instructions = Vec::new();
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut line_no = 0;
+        for (i, line) in src.lines().enumerate() {
+            line_no = i + 1;
+            let err = |message: String| ParseError {
+                line: line_no,
+                message,
+            };
+            let line = match line.split_once('#') {
+                Some((code, _comment)) => code.trim(),
+                None => line.trim(),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            let (keyword, rest) = line
+                .split_once(char::is_whitespace)
+                .map_or((line, ""), |(k, r)| (k, r.trim()));
+            match keyword {
+                "if" => {
+                    let expr = parse_expr(rest).map_err(err)?;
+                    blocks.push(Block {
+                        pending: Some(instructions.len()),
+                        ends: Vec::new(),
+                        has_else: false,
+                    });
+                    instructions.push(Instruction::JumpUnless(expr, 0));
+                }
+                "elif" | "else" => {
+                    let block = match blocks.last_mut() {
+                        Some(block) if !block.has_else => block,
+                        _ => return Err(err(format!("unexpected \"{keyword}\""))),
+                    };
+                    let expr = match keyword {
+                        "elif" => Some(parse_expr(rest).map_err(err)?),
+                        _ if rest.is_empty() => None,
+                        _ => return Err(err(String::from("unexpected tokens after \"else\""))),
+                    };
+                    block.ends.push(instructions.len());
+                    instructions.push(Instruction::Jump(0));
+                    if let Some(pending) = block.pending.take() {
+                        patch(&mut instructions, pending);
+                    }
+                    match expr {
+                        Some(expr) => {
+                            block.pending = Some(instructions.len());
+                            instructions.push(Instruction::JumpUnless(expr, 0));
+                        }
+                        None => block.has_else = true,
+                    }
+                }
+                "end" => {
+                    let Some(block) = blocks.pop() else {
+                        return Err(err(String::from("unexpected \"end\"")));
+                    };
+                    for jump in block.pending.into_iter().chain(block.ends) {
+                        patch(&mut instructions, jump);
+                    }
+                }
+                _ => instructions.push(Instruction::Cast(parse_action(line).map_err(err)?)),
+            }
+        }
+        if !blocks.is_empty() {
+            return Err(ParseError {
+                line: line_no,
+                message: String::from("missing \"end\""),
+            });
+        }
+        Ok(Self { instructions })
+    }
+}
+
+/// 将跳转指令的目标设为当前指令末尾
+fn patch(instructions: &mut [Instruction], at: usize) {
+    let len = instructions.len();
+    match &mut instructions[at] {
+        Instruction::JumpUnless(_, target) | Instruction::Jump(target) => *target = len,
+        Instruction::Cast(_) => unreachable!(),
+    }
+}
+
+fn parse_action(name: &str) -> Result<Actions, String> {
+    let unquoted = name.trim_matches('"');
+    let action = if unquoted.len() != name.len() {
+        find_action(unquoted)
+    } else {
+        Actions::deserialize(name.into_deserializer())
+            .map_err(|_: serde::de::value::Error| ())
+            .ok()
+            .or_else(|| find_action(name))
+    };
+    action.ok_or_else(|| format!("unknown action \"{unquoted}\""))
+}
+
+fn parse_condition(name: &str) -> Option<Condition> {
+    Some(match name {
+        "normal" => Condition::Normal,
+        "good" => Condition::Good,
+        "excellent" => Condition::Excellent,
+        "poor" => Condition::Poor,
+        "centered" => Condition::Centered,
+        "sturdy" => Condition::Sturdy,
+        "pliant" => Condition::Pliant,
+        "malleable" => Condition::Malleable,
+        "primed" => Condition::Primed,
+        "good_omen" => Condition::GoodOmen,
+        _ => return None,
+    })
+}
+
+fn parse_var(name: &str) -> Option<Var> {
+    Some(match name {
+        "progress" => Var::Progress,
+        "quality" => Var::Quality,
+        "durability" => Var::Durability,
+        "cp" => Var::CraftPoints,
+        "step" => Var::Step,
+        "inner_quiet" => Var::InnerQuiet,
+        "progress_left" => Var::ProgressLeft,
+        "quality_left" => Var::QualityLeft,
+        "muscle_memory" => Var::Buff(BuffKind::MuscleMemory),
+        "great_strides" => Var::Buff(BuffKind::GreatStrides),
+        "veneration" => Var::Buff(BuffKind::Veneration),
+        "innovation" => Var::Buff(BuffKind::Innovation),
+        "final_appraisal" => Var::Buff(BuffKind::FinalAppraisal),
+        "manipulation" => Var::Buff(BuffKind::Manipulation),
+        "waste_not" => Var::Buff(BuffKind::WasteNot),
+        "expedience" => Var::Buff(BuffKind::Expedience),
+        "heart_and_soul" => Var::Buff(BuffKind::HeartAndSoul),
+        "trained_perfection" => Var::Buff(BuffKind::TrainedPerfection),
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token<'a> {
+    Word(&'a str),
+    Op(Op),
+    LParen,
+    RParen,
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = src.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let (token, len) = match c {
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '=' | '!' | '<' | '>' => {
+                let (op, len) = match (c, rest[1..].starts_with('=')) {
+                    ('=', true) => (Op::Eq, 2),
+                    ('!', true) => (Op::Ne, 2),
+                    ('<', true) => (Op::Le, 2),
+                    ('>', true) => (Op::Ge, 2),
+                    ('<', false) => (Op::Lt, 1),
+                    ('>', false) => (Op::Gt, 1),
+                    _ => return Err(format!("unknown operator \"{c}\"")),
+                };
+                (Token::Op(op), len)
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '-' => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                    .unwrap_or(rest.len());
+                (Token::Word(&rest[..len]), len)
+            }
+            c => return Err(format!("unexpected character \"{c}\"")),
+        };
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_expr(src: &str) -> Result<Expr, String> {
+    let tokens = tokenize(src)?;
+    let mut parser = ExprParser { tokens, pos: 0 };
+    let expr = parser.or()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(expr),
+        Some(token) => Err(format!("unexpected token {token:?}")),
+    }
+}
+
+struct ExprParser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let matched = self.tokens.get(self.pos) == Some(&Token::Word(word));
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat_word("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat_word("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat_word("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(String::from("missing \")\"")),
+                }
+            }
+            Some(Token::Word("condition")) => {
+                let op = match self.next() {
+                    Some(Token::Op(op @ (Op::Eq | Op::Ne))) => op,
+                    _ => return Err(String::from("condition can only be compared with == or !=")),
+                };
+                match self.next() {
+                    Some(Token::Word(name)) => parse_condition(name)
+                        .map(|c| Expr::Condition(op, c))
+                        .ok_or_else(|| format!("unknown condition \"{name}\"")),
+                    _ => Err(String::from("missing condition name")),
+                }
+            }
+            Some(Token::Word(name)) => {
+                let var = parse_var(name).ok_or_else(|| format!("unknown variable \"{name}\""))?;
+                let Some(Token::Op(op)) = self.tokens.get(self.pos).cloned() else {
+                    return Ok(Expr::NonZero(var));
+                };
+                self.pos += 1;
+                match self.next() {
+                    Some(Token::Word(value)) => value
+                        .parse()
+                        .map(|value| Expr::Compare(var, op, value))
+                        .map_err(|_| format!("invalid number \"{value}\"")),
+                    _ => Err(String::from("missing number")),
+                }
+            }
+            Some(token) => Err(format!("unexpected token {token:?}")),
+            None => Err(String::from("missing expression")),
+        }
+    }
+}
+
+impl Op {
+    fn apply<T: PartialOrd>(self, lhs: T, rhs: T) -> bool {
+        match self {
+            Op::Eq => lhs == rhs,
+            Op::Ne => lhs != rhs,
+            Op::Lt => lhs < rhs,
+            Op::Le => lhs <= rhs,
+            Op::Gt => lhs > rhs,
+            Op::Ge => lhs >= rhs,
+        }
+    }
+}
+
+impl Var {
+    fn value(self, s: &Status) -> i64 {
+        let limited = |state: LimitedActionState| match state {
+            LimitedActionState::Active => 1,
+            _ => 0,
+        };
+        match self {
+            Var::Progress => s.progress as i64,
+            Var::Quality => s.quality as i64,
+            Var::Durability => s.durability as i64,
+            Var::CraftPoints => s.craft_points as i64,
+            Var::Step => s.step as i64,
+            Var::InnerQuiet => s.buffs.inner_quiet as i64,
+            Var::ProgressLeft => s.recipe.difficulty as i64 - s.progress as i64,
+            Var::QualityLeft => s.recipe.quality as i64 - s.quality as i64,
+            Var::Buff(kind) => match kind {
+                BuffKind::MuscleMemory => s.buffs.muscle_memory as i64,
+                BuffKind::GreatStrides => s.buffs.great_strides as i64,
+                BuffKind::Veneration => s.buffs.veneration as i64,
+                BuffKind::Innovation => s.buffs.innovation as i64,
+                BuffKind::FinalAppraisal => s.buffs.final_appraisal as i64,
+                BuffKind::Manipulation => s.buffs.manipulation as i64,
+                BuffKind::WasteNot => s.buffs.wast_not as i64,
+                BuffKind::Expedience => s.buffs.expedience as i64,
+                BuffKind::HeartAndSoul => limited(s.buffs.heart_and_soul),
+                BuffKind::TrainedPerfection => limited(s.buffs.trained_perfection),
+            },
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, s: &Status) -> bool {
+        match self {
+            Expr::And(lhs, rhs) => lhs.eval(s) && rhs.eval(s),
+            Expr::Or(lhs, rhs) => lhs.eval(s) || rhs.eval(s),
+            Expr::Not(expr) => !expr.eval(s),
+            Expr::Compare(var, op, value) => op.apply(var.value(s), *value),
+            Expr::Condition(op, condition) => op.apply(s.condition == *condition, true),
+            Expr::NonZero(var) => var.value(s) != 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::{Actions, Condition, Status};
+
+    use super::Rotation;
+    use crate::test_status;
+
+    fn run(rotation: &Rotation, status: &Status) -> Vec<Actions> {
+        let mut pc = 0;
+        std::iter::from_fn(|| rotation.next_action(&mut pc, status)).collect()
+    }
+
+    #[test]
+    fn branches() {
+        let rotation: Rotation = "
+            muscle_memory # 开局
+            if condition == good or condition == excellent
+                precise_touch
+            elif not innovation and cp >= 18
+                \"Innovation\"
+            else
+                basic_touch
+            end
+            制作
+        "
+        .parse()
+        .unwrap();
+
+        let mut status = test_status();
+        assert_eq!(
+            run(&rotation, &status),
+            [
+                Actions::MuscleMemory,
+                Actions::Innovation,
+                Actions::BasicSynthesis
+            ]
+        );
+        status.condition = Condition::Good;
+        assert_eq!(
+            run(&rotation, &status),
+            [
+                Actions::MuscleMemory,
+                Actions::PreciseTouch,
+                Actions::BasicSynthesis
+            ]
+        );
+        status.condition = Condition::Normal;
+        status.craft_points = 0;
+        assert_eq!(
+            run(&rotation, &status),
+            [
+                Actions::MuscleMemory,
+                Actions::BasicTouch,
+                Actions::BasicSynthesis
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let lines = |src: &str| src.parse::<Rotation>().unwrap_err().line;
+        assert_eq!(lines("basic_touch\nnot_an_action"), 2);
+        assert_eq!(lines("if cp > 10\nbasic_touch"), 2);
+        assert_eq!(lines("else"), 1);
+        assert_eq!(lines("if condition > good\nend"), 1);
+        assert_eq!(lines("if cp >= \nend"), 1);
+    }
+}
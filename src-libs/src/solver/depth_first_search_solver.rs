@@ -16,21 +16,46 @@
 
 use ffxiv_crafting::{Actions, Status};
 
-use crate::solver::Score;
+use crate::solver::{CancelToken, ProgressSink, Score};
+
+/// 每搜索这么多个节点报告一次进度
+#[cfg(not(target_family = "wasm"))]
+const PROGRESS_INTERVAL: usize = 1 << 16;
 
 /// 进行一次深度优先搜索（DFS）
 ///
 /// status为开始制作时的初始状态
 /// maximum_depth为限制最深搜索深度
+/// 搜索的节点数和找到的更优解会通知sink，被cancel中止时返回已找到的最优解
 #[cfg(not(target_family = "wasm"))]
-pub fn solve(status: Status, maximum_depth: usize, specialist: bool) -> Vec<Actions> {
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    fn search(
-        status: Status,
-        actions: Vec<Actions>,
-        aval_worker_num: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+pub fn solve(
+    status: Status,
+    maximum_depth: usize,
+    specialist: bool,
+    cancel: &CancelToken,
+    sink: &dyn ProgressSink,
+) -> Vec<Actions> {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+    use std::thread::Scope;
+
+    struct Shared<'a> {
+        aval_worker_num: AtomicUsize,
+        searched: AtomicUsize,
+        best_score: Mutex<Score>,
         maximum_depth: usize,
         specialist: bool,
+        cancel: &'a CancelToken,
+        sink: &'a dyn ProgressSink,
+    }
+
+    fn search<'scope, 'env>(
+        scope: &'scope Scope<'scope, 'env>,
+        shared: &'env Shared<'env>,
+        status: Status,
+        actions: Vec<Actions>,
     ) -> (Score, Vec<Actions>) {
         let mut threads = Vec::new();
         let mut best_actions = actions.clone();
@@ -49,19 +74,27 @@ pub fn solve(status: Status, maximum_depth: usize, specialist: bool) -> Vec<Acti
             *stack_seq.last_mut().unwrap() = next_action;
 
             if !matches!(next_action, Actions::FinalAppraisal if status.buffs.final_appraisal == 0)
-                && (!matches!(next_action, Actions::HeartAndSoul) || specialist)
-                && stack_seq.len() <= maximum_depth
+                && (!matches!(next_action, Actions::HeartAndSoul) || shared.specialist)
+                && stack_seq.len() <= shared.maximum_depth
                 && status.is_action_allowed(next_action).is_ok()
             {
+                if shared.cancel.is_cancelled() {
+                    break;
+                }
+                let searched = shared.searched.fetch_add(1, Ordering::Relaxed) + 1;
+                if searched.is_multiple_of(PROGRESS_INTERVAL) {
+                    shared.sink.progress(searched);
+                }
                 let mut new_s = status.clone();
                 new_s.cast_action(next_action);
                 if !new_s.is_finished() {
                     if best_score.quality != new_s.recipe.quality
                         || best_score.steps >= new_s.step as u16
                     {
-                        let num = aval_worker_num.load(Ordering::Relaxed);
+                        let num = shared.aval_worker_num.load(Ordering::Relaxed);
                         if num > 0
-                            && aval_worker_num
+                            && shared
+                                .aval_worker_num
                                 .compare_exchange(
                                     num,
                                     num - 1,
@@ -70,11 +103,9 @@ pub fn solve(status: Status, maximum_depth: usize, specialist: bool) -> Vec<Acti
                                 )
                                 .is_ok()
                         {
-                            let (status, actions, aval_worker_num) =
-                                (new_s.clone(), stack_seq.clone(), aval_worker_num.clone());
-                            threads.push(std::thread::spawn(move || {
-                                search(status, actions, aval_worker_num, maximum_depth, specialist)
-                            }));
+                            let (status, actions) = (new_s.clone(), stack_seq.clone());
+                            threads
+                                .push(scope.spawn(move || search(scope, shared, status, actions)));
                         } else {
                             stack.push((new_s, ACTION_LIST.into_iter()));
                             stack_seq.push(next_action);
@@ -85,11 +116,17 @@ pub fn solve(status: Status, maximum_depth: usize, specialist: bool) -> Vec<Acti
                     if score > best_score {
                         best_score = score;
                         best_actions = stack_seq.clone();
+                        // 只有优于所有线程的结果才作为中间结果报告
+                        let mut global_best = shared.best_score.lock().unwrap();
+                        if score > *global_best {
+                            *global_best = score;
+                            shared.sink.intermediate(&best_actions);
+                        }
                     }
                 }
             }
         }
-        aval_worker_num.fetch_add(1, Ordering::Relaxed);
+        shared.aval_worker_num.fetch_add(1, Ordering::Relaxed);
         for (score, actions) in threads.into_iter().map(|x| x.join().unwrap()) {
             if score > best_score {
                 best_score = score;
@@ -99,33 +136,42 @@ pub fn solve(status: Status, maximum_depth: usize, specialist: bool) -> Vec<Acti
         (best_score, best_actions)
     }
 
-    let num = num_cpus::get();
-    let aval_worker_num = std::sync::Arc::new(AtomicUsize::new(num));
-    aval_worker_num.fetch_sub(1, Ordering::Relaxed);
-    let (_best_score, best_actions) = search(
-        status.clone(),
-        Vec::new(),
-        aval_worker_num,
+    let shared = Shared {
+        aval_worker_num: AtomicUsize::new(num_cpus::get() - 1),
+        searched: AtomicUsize::new(0),
+        best_score: Mutex::new(Score::from(&status)),
         maximum_depth,
         specialist,
-    );
+        cancel,
+        sink,
+    };
+    let (_best_score, best_actions) =
+        std::thread::scope(|scope| search(scope, &shared, status, Vec::new()));
     best_actions
 }
 
 #[cfg(target_family = "wasm")]
-pub fn solve(status: Status, maximum_depth: usize, specialist: bool) -> Vec<Actions> {
-    let mut stack_seq: Vec<Actions> = Vec::new();
-    let mut best_actions: Vec<Actions> = Vec::new();
-    let mut best_score = Score::from(&status);
+pub fn solve(
+    status: Status,
+    maximum_depth: usize,
+    specialist: bool,
+    cancel: &CancelToken,
+    sink: &dyn ProgressSink,
+) -> Vec<Actions> {
+    // wasm中只有单线程，取消通过终止整个Worker实现，这里只检查一下标记
     fn search(
         status: &Status,
         stack_seq: &mut Vec<Actions>,
         maximum_depth: usize,
-        best_score: &mut Score,
-        best_actions: &mut Vec<Actions>,
+        best: &mut (Score, Vec<Actions>),
         specialist: bool,
+        cancel: &CancelToken,
+        sink: &dyn ProgressSink,
     ) {
         for next_action in ACTION_LIST {
+            if cancel.is_cancelled() {
+                return;
+            }
             if !matches!(next_action, Actions::AdvancedTouch if status.buffs.observed == 0 && status.buffs.touch_combo_stage != 2)
                 && !matches!(next_action, Actions::FinalAppraisal if status.buffs.final_appraisal == 0)
                 && (!matches!(next_action, Actions::HeartAndSoul) || specialist)
@@ -138,20 +184,21 @@ pub fn solve(status: Status, maximum_depth: usize, specialist: bool) -> Vec<Acti
                 new_s.cast_action(next_action);
                 if new_s.is_finished() {
                     let score = Score::from((&new_s, stack_seq.len()));
-                    if score > *best_score {
-                        *best_score = score;
-                        *best_actions = stack_seq.clone();
+                    if score > best.0 {
+                        *best = (score, stack_seq.clone());
+                        sink.intermediate(&best.1);
                     }
-                } else if best_score.quality != new_s.recipe.quality
-                    || best_score.steps >= new_s.step as u16
+                } else if best.0.quality != new_s.recipe.quality
+                    || best.0.steps >= new_s.step as u16
                 {
                     search(
                         &new_s,
                         stack_seq,
                         maximum_depth,
-                        best_score,
-                        best_actions,
+                        best,
                         specialist,
+                        cancel,
+                        sink,
                     );
                 }
 
@@ -159,15 +206,17 @@ pub fn solve(status: Status, maximum_depth: usize, specialist: bool) -> Vec<Acti
             }
         }
     }
+    let mut best = (Score::from(&status), Vec::new());
     search(
         &status,
-        &mut stack_seq,
+        &mut Vec::new(),
         maximum_depth,
-        &mut best_score,
-        &mut best_actions,
+        &mut best,
         specialist,
+        cancel,
+        sink,
     );
-    best_actions
+    best.1
 }
 
 /// 搜索的技能列表
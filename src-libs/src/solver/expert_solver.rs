@@ -0,0 +1,334 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use ffxiv_crafting::{Actions, Status};
+use serde::{Deserialize, Serialize};
+
+use super::{CancelToken, Solver};
+use crate::analyzer::exact_distribution::{transitions, StateKey};
+
+/// 求解器优化的目标
+#[derive(Default, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    /// 最大化推满进展的概率
+    Success,
+    /// 最大化出HQ的概率
+    #[default]
+    HighQuality,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Suggestion {
+    pub action: Actions,
+    /// 释放该技能后目标的期望值，即估计的成功率或HQ率
+    pub value: f64,
+}
+
+/// 面向高难度配方的反应式求解器。
+///
+/// 其他求解器从初始状态出发给出一条固定的技能序列，而这个求解器根据包含球色在内的当前状态，
+/// 只给出下一步应当释放的技能，因此每一步都需要以新的状态重新调用。
+/// 内部对技能的成败和球色的变化做有限深度的期望最大化搜索，到达深度限制时用启发式函数估计剩余制作的价值
+pub struct ExpertSolver {
+    depth: usize,
+    specialist: bool,
+    objective: Objective,
+    cancel: CancelToken,
+}
+
+impl ExpertSolver {
+    /// 搜索深度的上限，更深的搜索耗时过长
+    pub const MAX_DEPTH: usize = 3;
+
+    /// `depth`为向后搜索的步数，每增加一步耗时约增加两个数量级，一般取2即可。
+    /// 超过[`Self::MAX_DEPTH`]时按上限搜索
+    pub fn new(depth: usize, specialist: bool, objective: Objective, cancel: CancelToken) -> Self {
+        Self {
+            depth: depth.clamp(1, Self::MAX_DEPTH),
+            specialist,
+            objective,
+            cancel,
+        }
+    }
+
+    /// 计算当前状态下期望价值最高的技能，制作已经结束或没有可用技能时返回`None`。
+    /// 被`cancel`中止时返回的结果没有意义
+    pub fn suggest(&self, s: &Status) -> Option<Suggestion> {
+        if s.is_finished() {
+            return None;
+        }
+        let mut cache = HashMap::new();
+        let mut best: Option<Suggestion> = None;
+        for action in self.candidates(s) {
+            let value = self.expect(s, action, self.depth, &mut cache);
+            if best.is_none_or(|b| value > b.value) {
+                best = Some(Suggestion { action, value });
+            }
+        }
+        best
+    }
+
+    fn candidates<'a>(&'a self, s: &'a Status) -> impl Iterator<Item = Actions> + 'a {
+        ACTION_LIST.into_iter().filter(move |&action| {
+            match action {
+                Actions::HeartAndSoul | Actions::CarefulObservation if !self.specialist => {
+                    return false
+                }
+                Actions::FinalAppraisal if s.buffs.final_appraisal > 0 => return false,
+                _ => {}
+            }
+            s.is_action_allowed(action).is_ok()
+        })
+    }
+
+    fn expect(
+        &self,
+        s: &Status,
+        action: Actions,
+        depth: usize,
+        cache: &mut HashMap<(StateKey, usize), f64>,
+    ) -> f64 {
+        transitions(s, action)
+            .into_iter()
+            .map(|(s, p)| p * self.search(&s, depth - 1, cache))
+            .sum()
+    }
+
+    fn search(&self, s: &Status, depth: usize, cache: &mut HashMap<(StateKey, usize), f64>) -> f64 {
+        if s.is_finished() {
+            return self.terminal(s);
+        }
+        if depth == 0 || self.cancel.is_cancelled() {
+            return self.estimate(s);
+        }
+        let key = (StateKey::new(0, s), depth);
+        if let Some(&value) = cache.get(&key) {
+            return value;
+        }
+        let value = self
+            .candidates(s)
+            .map(|action| self.expect(s, action, depth, cache))
+            .fold(0.0, f64::max);
+        cache.insert(key, value);
+        value
+    }
+
+    /// 制作结束时的价值
+    fn terminal(&self, s: &Status) -> f64 {
+        if s.progress < s.recipe.difficulty {
+            return 0.0;
+        }
+        self.value(s, 1.0, s.quality)
+    }
+
+    /// 粗略估计从未结束的状态继续制作的价值。
+    /// 先按制作力和耐久是否足够用俭约制作推满进展估计成功率，
+    /// 再把剩余的资源全部换算成加工估计能达到的品质
+    fn estimate(&self, s: &Status) -> f64 {
+        let progress_left = s.recipe.difficulty.saturating_sub(s.progress) as f64;
+        let efficiency = if s.attributes.level < 82 { 1.5 } else { 1.8 };
+        let syntheses = (progress_left / (s.caches.base_synth as f64 * efficiency).max(1.0)).ceil();
+        // 最后一步制作允许耗尽耐久
+        let durability_needed = (syntheses - 1.0).max(0.0) * 10.0 + 1.0;
+        let durability = s.durability as f64 + s.buffs.manipulation as f64 * 5.0;
+        // 缺少的耐久按掌握的效率（96制作力恢复40耐久）用制作力补足
+        let craft_points = s.craft_points as f64
+            - syntheses * 7.0
+            - (durability_needed - durability).max(0.0) * DURABILITY_COST;
+        let success = (1.0 + craft_points.min(0.0) / 100.0).max(0.0);
+
+        let spare =
+            craft_points.max(0.0) + (durability - durability_needed).max(0.0) * DURABILITY_COST;
+        // 按每次加工消耗18制作力和10耐久、享受改革加成估计剩余的加工次数
+        let touches = spare / (18.0 + 10.0 * DURABILITY_COST);
+        let inner_quiet = (s.buffs.inner_quiet as f64 + touches / 2.0).min(10.0);
+        let gain = touches * s.caches.base_touch as f64 * 1.5 * (1.0 + inner_quiet / 10.0);
+        let quality = (s.quality as f64 + gain).min(s.recipe.quality as f64) as u32;
+        self.value(s, success, quality)
+    }
+
+    fn value(&self, s: &Status, success: f64, quality: u32) -> f64 {
+        match self.objective {
+            Objective::Success => success,
+            Objective::HighQuality => {
+                let mut s = s.clone();
+                s.quality = quality;
+                let hq = s.high_quality_probability().unwrap_or(0) as f64 / 100.0;
+                // 无论如何都无法出HQ时，仍然优先保证制作成功
+                success * (hq + 1e-3)
+            }
+        }
+    }
+}
+
+impl Solver for ExpertSolver {
+    fn init(&mut self) {}
+
+    fn read(&self, s: &Status) -> Option<Actions> {
+        self.suggest(s).map(|suggestion| suggestion.action)
+    }
+}
+
+/// 每点耐久折合的制作力
+const DURABILITY_COST: f64 = 96.0 / 40.0;
+
+/// 搜索的技能列表，排在前面的技能在期望价值相同时优先
+const ACTION_LIST: [Actions; 36] = [
+    Actions::CarefulSynthesis,
+    Actions::Groundwork,
+    Actions::PrudentSynthesis,
+    Actions::IntensiveSynthesis,
+    Actions::RapidSynthesis,
+    Actions::BasicSynthesis,
+    Actions::MuscleMemory,
+    Actions::DelicateSynthesis,
+    Actions::PreparatoryTouch,
+    Actions::PrudentTouch,
+    Actions::PreciseTouch,
+    Actions::BasicTouch,
+    Actions::StandardTouch,
+    Actions::AdvancedTouch,
+    Actions::RefinedTouch,
+    Actions::HastyTouch,
+    Actions::DaringTouch,
+    Actions::TrainedFinesse,
+    Actions::ByregotsBlessing,
+    Actions::Reflect,
+    Actions::TrainedEye,
+    Actions::Innovation,
+    Actions::Veneration,
+    Actions::GreatStrides,
+    Actions::QuickInnovation,
+    Actions::WasteNot,
+    Actions::WasteNotII,
+    Actions::Manipulation,
+    Actions::MastersMend,
+    Actions::ImmaculateMend,
+    Actions::TrainedPerfection,
+    Actions::TricksOfTheTrade,
+    Actions::Observe,
+    Actions::FinalAppraisal,
+    Actions::CarefulObservation,
+    Actions::HeartAndSoul,
+];
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::{Actions, Condition, Status};
+
+    use super::{ExpertSolver, Objective};
+    use crate::{solver::CancelToken, test_status};
+
+    #[test]
+    fn suggest_until_finished() {
+        let solver = ExpertSolver::new(1, false, Objective::HighQuality, CancelToken::new());
+        let mut status = test_status();
+        while let Some(suggestion) = solver.suggest(&status) {
+            assert!((0.0..=1.001).contains(&suggestion.value));
+            assert!(status.is_action_allowed(suggestion.action).is_ok());
+            status.cast_action(suggestion.action);
+            assert!(status.step < 100);
+        }
+        assert!(status.is_finished());
+    }
+
+    /// 专家配方中途的状态，还差`synth`倍基础进展即可推满
+    fn expert(condition: Condition, durability: u16, craft_points: i32, synth: f32) -> Status {
+        let mut s = test_status();
+        // 白、红、黄、蓝、绿、深蓝、紫、粉八种球色
+        s.recipe.conditions_flag = 1011;
+        s.step = 5;
+        s.condition = condition;
+        s.durability = durability;
+        s.craft_points = craft_points;
+        s.progress = s.recipe.difficulty - (s.caches.base_synth * synth) as u16;
+        s
+    }
+
+    /// 分别在指定球色和白球下求解，只有前者能确保推满进展，返回前者建议的技能
+    fn suggest_in(condition: Condition, depth: usize, s: Status) -> Actions {
+        let solver = ExpertSolver::new(depth, false, Objective::Success, CancelToken::new());
+        let suggestion = solver.suggest(&s).unwrap();
+        let normal = solver
+            .suggest(&Status {
+                condition: Condition::Normal,
+                ..s
+            })
+            .unwrap();
+        assert!(suggestion.value > 0.99, "{condition:?}: {suggestion:?}");
+        assert!(normal.value < 0.99, "{condition:?}: {normal:?}");
+        suggestion.action
+    }
+
+    #[test]
+    fn sturdy_allows_two_syntheses() {
+        // 耐久只够一次制作，蓝球时耐久消耗减半，可以再制作一次
+        let action = suggest_in(Condition::Sturdy, 2, expert(Condition::Sturdy, 10, 7, 2.5));
+        assert!(matches!(
+            action,
+            Actions::CarefulSynthesis | Actions::BasicSynthesis | Actions::RapidSynthesis
+        ));
+    }
+
+    #[test]
+    fn pliant_affords_durability() {
+        // 制作力只有在绿球时才够使用俭约或精修
+        let action = suggest_in(Condition::Pliant, 3, expert(Condition::Pliant, 10, 48, 2.0));
+        assert!(matches!(action, Actions::WasteNot | Actions::MastersMend));
+    }
+
+    #[test]
+    fn malleable_finishes_with_one_synthesis() {
+        // 深蓝球时进展提高50%，一次模范制作即可推满
+        let action = suggest_in(
+            Condition::Malleable,
+            2,
+            expert(Condition::Malleable, 10, 7, 2.0),
+        );
+        assert_eq!(action, Actions::CarefulSynthesis);
+    }
+
+    #[test]
+    fn good_omen_observes_for_intensive_synthesis() {
+        // 粉球的下一回合必定是红球，观察后可以使用集中制作
+        let action = suggest_in(
+            Condition::GoodOmen,
+            2,
+            expert(Condition::GoodOmen, 10, 13, 3.0),
+        );
+        assert_eq!(action, Actions::Observe);
+    }
+
+    #[test]
+    fn depth_is_clamped() {
+        let solver = ExpertSolver::new(10, false, Objective::Success, CancelToken::new());
+        assert_eq!(solver.depth, ExpertSolver::MAX_DEPTH);
+    }
+
+    #[test]
+    fn primed_still_finishes() {
+        // 紫球只延长增益的持续时间，不影响收尾的选择
+        let solver = ExpertSolver::new(2, false, Objective::Success, CancelToken::new());
+        let suggestion = solver
+            .suggest(&expert(Condition::Primed, 10, 7, 1.5))
+            .unwrap();
+        assert_eq!(suggestion.action, Actions::CarefulSynthesis);
+        assert!(suggestion.value > 0.99);
+    }
+}
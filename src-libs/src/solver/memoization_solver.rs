@@ -14,10 +14,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use app_libs::ffxiv_crafting::{Actions, Buffs, Status};
+use ffxiv_crafting::{Actions, Buffs, Status};
 use micro_ndarray::Array;
 use std::cell::Cell;
 
+use super::CancelToken;
+
 #[derive(Clone, Copy, Default, Debug)]
 pub(crate) struct Slot {
     pub(crate) score: u32,
@@ -31,6 +33,7 @@ pub struct Solver {
     allow_mn: bool,
     allow_wn: usize,
     allow_obz: bool,
+    cancel: CancelToken,
     touch_caches: Array<Cell<Slot>, 11>,
 }
 
@@ -65,7 +68,14 @@ impl Solver {
         (Actions::ImmaculateMend, 0),
     ];
 
-    pub(crate) fn new(init_status: Status, mn: bool, wn: usize, obz: bool) -> Self {
+    /// 表格在求解时逐步填充，`cancel`被设置后不再给出任何技能，此后表格中的结果不再可靠
+    pub(crate) fn new(
+        init_status: Status,
+        mn: bool,
+        wn: usize,
+        obz: bool,
+        cancel: CancelToken,
+    ) -> Self {
         let size = [
             obz as usize * Self::MAX_OBSERVE + 1,
             Self::MAX_QUICK_INNOVAATION_USED + 1,
@@ -93,6 +103,7 @@ impl Solver {
             allow_mn: mn,
             allow_wn: wn,
             allow_obz: obz,
+            cancel,
             touch_caches,
             init_status,
         }
@@ -120,6 +131,9 @@ impl Solver {
                 return slot;
             }
         }
+        if self.cancel.is_cancelled() {
+            return Slot::default();
+        }
         let mut best = Slot {
             score: 0,
             steps: 0,
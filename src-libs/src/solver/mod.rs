@@ -15,14 +15,22 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 pub mod depth_first_search_solver;
+pub mod expert_solver;
+mod memoization_solver;
+pub mod muscle_memory_solver;
 pub mod normal_progress_solver;
+#[cfg(not(target_family = "wasm"))]
+pub mod portfolio;
+pub mod raphael;
 pub mod reflect_solver;
+pub mod registry;
 pub mod rika_solver;
-pub mod raphael;
+pub mod rika_tnze_solver;
 
 use std::cmp::Ordering;
 
 use ffxiv_crafting::{Actions, Attributes, Recipe, Status};
+use raphael_solvers::AtomicFlag;
 
 #[derive(Hash, Eq, PartialEq, Clone)]
 pub struct SolverHash {
@@ -30,6 +38,38 @@ pub struct SolverHash {
     pub recipe: Recipe,
 }
 
+/// 用于中止正在运行的求解器，克隆得到的副本共享同一个标记
+#[derive(Default, Clone, Debug)]
+pub struct CancelToken(AtomicFlag);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.set();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_set()
+    }
+
+    pub(crate) fn flag(&self) -> AtomicFlag {
+        self.0.clone()
+    }
+}
+
+/// 接收求解器运行过程中的进度和中间结果，默认实现忽略所有通知
+pub trait ProgressSink: Sync {
+    /// 已完成的工作量，其单位由各个求解器自行决定，只保证单调增加
+    fn progress(&self, _done: usize) {}
+    /// 找到了比之前更好的技能序列
+    fn intermediate(&self, _actions: &[Actions]) {}
+}
+
+impl ProgressSink for () {}
+
 pub trait Solver {
     fn init(&mut self);
     fn read(&self, s: &Status) -> Option<Actions>;
@@ -50,7 +90,7 @@ pub trait Solver {
     }
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Clone, Copy)]
 pub struct Score {
     pub quality: u32,
     pub prgress: u16,
@@ -14,21 +14,20 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use app_libs::{
-    ffxiv_crafting::{Actions, Status},
-    solver::{Score, Solver},
-};
+use ffxiv_crafting::{Actions, Status};
 
-use crate::memoization_solver::Solver as MemorizationSolver;
+use super::{memoization_solver::Solver as MemorizationSolver, CancelToken, Score, Solver};
 
 pub struct PreprogressSolver {
     quality_solver: MemorizationSolver,
+    cancel: CancelToken,
 }
 
 impl PreprogressSolver {
-    pub fn new(init_status: Status, mn: bool, wn: usize, obz: bool) -> Self {
+    pub fn new(init_status: Status, mn: bool, wn: usize, obz: bool, cancel: CancelToken) -> Self {
         Self {
-            quality_solver: MemorizationSolver::new(init_status, mn, wn, obz),
+            quality_solver: MemorizationSolver::new(init_status, mn, wn, obz, cancel.clone()),
+            cancel,
         }
     }
 }
@@ -61,6 +60,9 @@ impl Solver for PreprogressSolver {
 
         let mut actions = Vec::new();
         'rs: for cp in (0..=s.craft_points).rev() {
+            if self.cancel.is_cancelled() {
+                break;
+            }
             for du in (1..=s.durability).filter(|x| x % 5 == 0).rev() {
                 let mut s = s.clone();
                 s.craft_points = cp;
@@ -16,16 +16,29 @@
 
 use ffxiv_crafting::{Actions, Status};
 
-pub fn solve(status: Status, maximum_depth: usize, specialist: bool) -> Vec<Actions> {
-    let (_, mut actions) = search(status, maximum_depth, specialist);
+use crate::solver::CancelToken;
+
+/// 被`cancel`中止时尽快返回，此时的结果不保证最优
+pub fn solve(
+    status: Status,
+    maximum_depth: usize,
+    specialist: bool,
+    cancel: &CancelToken,
+) -> Vec<Actions> {
+    let (_, mut actions) = search(status, maximum_depth, specialist, cancel);
     actions.reverse();
     actions
 }
 
-fn search(status: Status, maximum_depth: usize, specialist: bool) -> (i32, Vec<Actions>) {
+fn search(
+    status: Status,
+    maximum_depth: usize,
+    specialist: bool,
+    cancel: &CancelToken,
+) -> (i32, Vec<Actions>) {
     if status.progress == status.recipe.difficulty {
         return (status.step, Vec::new());
-    } else if status.durability <= 0 || maximum_depth == 0 {
+    } else if status.durability <= 0 || maximum_depth == 0 || cancel.is_cancelled() {
         return (0, Vec::new());
     }
     SKILL_LIST
@@ -38,7 +51,7 @@ fn search(status: Status, maximum_depth: usize, specialist: bool) -> (i32, Vec<A
         .filter_map(|x| {
             let mut new_s = status.clone();
             new_s.cast_action(x);
-            let (steps, mut actions) = search(new_s, maximum_depth - 1, specialist);
+            let (steps, mut actions) = search(new_s, maximum_depth - 1, specialist, cancel);
             if steps == 0 {
                 None
             } else {
@@ -0,0 +1,204 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    cmp::Ordering,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use ffxiv_crafting::{Actions, Status};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    registry::{self, SolverConfig},
+    CancelToken, Score,
+};
+use crate::{simulate, CastErrorPos};
+
+/// 对各求解器的结果排序的依据
+#[derive(Default, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    /// 按[`Score`]排序，即依次比较进展、品质和步数
+    #[default]
+    Score,
+    /// 品质优先，超出配方最高品质的部分不计，品质相同时步数少的优先
+    Quality,
+    /// 步数少的优先，步数相同时品质高的优先
+    Steps,
+}
+
+impl Objective {
+    fn compare(&self, a: &PortfolioEntry, b: &PortfolioEntry) -> Ordering {
+        let (sa, sb) = (&a.status, &b.status);
+        a.valid.cmp(&b.valid).then_with(|| match self {
+            Objective::Score => Score::from(sa).cmp(&Score::from(sb)),
+            Objective::Quality => (sa.quality.min(sa.recipe.quality))
+                .cmp(&sb.quality.min(sb.recipe.quality))
+                .then_with(|| sa.step.cmp(&sb.step).reverse()),
+            Objective::Steps => sa
+                .step
+                .cmp(&sb.step)
+                .reverse()
+                .then_with(|| sa.quality.cmp(&sb.quality)),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct PortfolioEntry {
+    pub config: SolverConfig,
+    pub actions: Vec<Actions>,
+    /// 按求解结果模拟得到的最终状态
+    pub status: Status,
+    /// 模拟过程中出现的错误
+    pub errors: Vec<CastErrorPos>,
+    /// 模拟没有出错并且推满了进展
+    pub valid: bool,
+    /// 求解器无法运行或在时限内没有结束时的原因，此时`actions`为空
+    pub error: Option<String>,
+    /// 求解耗时的毫秒数
+    pub elapsed: u64,
+}
+
+/// 单个求解器的求解结果和耗时
+type Outcome = (Result<Vec<Actions>, String>, Duration);
+
+/// 在各自的线程中用多个求解器同时求解同一个状态，所有求解器共用`time_limit`的时间。
+///
+/// 每个结果都会经过模拟检验，返回的列表按`objective`从好到坏排列，无效的结果排在最后。
+/// 超时或被`cancel`中止时会通知仍在运行的求解器停止，但不等待它们的线程退出
+pub fn solve(
+    status: Status,
+    configs: Vec<SolverConfig>,
+    time_limit: Duration,
+    objective: Objective,
+    cancel: &CancelToken,
+) -> Vec<PortfolioEntry> {
+    let start = Instant::now();
+    let stop = CancelToken::new();
+    let (tx, rx) = mpsc::channel();
+    let mut results: Vec<Option<Outcome>> = configs.iter().map(|_| None).collect();
+    let mut running = 0;
+    for (i, config) in configs.iter().enumerate() {
+        if let Err(err) = config.check(&status) {
+            results[i] = Some((Err(err), Duration::ZERO));
+            continue;
+        }
+        let (tx, stop) = (tx.clone(), stop.clone());
+        let (status, config) = (status.clone(), config.clone());
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            let result = registry::solve(&config, status, &stop, &());
+            let _ = tx.send((i, result, start.elapsed()));
+        });
+        running += 1;
+    }
+
+    // 定期醒来检查外部的取消信号
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+    while running > 0 && !cancel.is_cancelled() {
+        let Some(remaining) = time_limit.checked_sub(start.elapsed()) else {
+            break;
+        };
+        match rx.recv_timeout(remaining.min(POLL_INTERVAL)) {
+            Ok((i, result, elapsed)) => {
+                results[i] = Some((result, elapsed));
+                running -= 1;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    stop.cancel();
+    // 被中止的求解器返回的结果没有意义，直接丢弃
+    let unfinished = if cancel.is_cancelled() {
+        "solve-cancelled"
+    } else {
+        "solve-timeout"
+    };
+
+    let mut entries: Vec<_> = configs
+        .into_iter()
+        .zip(results)
+        .map(|(config, result)| {
+            let (result, elapsed) =
+                result.unwrap_or_else(|| (Err(String::from(unfinished)), start.elapsed()));
+            let (actions, error) = match result {
+                Ok(actions) => (actions, None),
+                Err(err) => (Vec::new(), Some(err)),
+            };
+            let simulated = simulate(status.clone(), actions.clone());
+            let valid = error.is_none()
+                && simulated.errors.is_empty()
+                && simulated.status.progress >= simulated.status.recipe.difficulty;
+            PortfolioEntry {
+                config,
+                actions,
+                status: simulated.status,
+                errors: simulated.errors,
+                valid,
+                error,
+                elapsed: elapsed.as_millis() as u64,
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| objective.compare(b, a));
+    entries
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use ffxiv_crafting::Actions;
+
+    use super::{solve, Objective};
+    use crate::{
+        solver::{registry::SolverConfig, CancelToken},
+        test_status,
+    };
+
+    #[test]
+    fn invalid_results_are_ranked_last() {
+        let mut status = test_status();
+        status.cast_action(Actions::MuscleMemory);
+        let configs = vec![
+            SolverConfig::Rika,
+            SolverConfig::NormalProgress {
+                depth: 4,
+                specialist: false,
+            },
+        ];
+        let entries = solve(
+            status,
+            configs,
+            Duration::from_secs(60),
+            Objective::Score,
+            &CancelToken::new(),
+        );
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].valid);
+        assert!(matches!(
+            entries[0].config,
+            SolverConfig::NormalProgress { .. }
+        ));
+        // 配方求解器不支持从制作中途开始
+        assert!(!entries[1].valid);
+        assert!(entries[1].error.is_some());
+    }
+}
@@ -14,11 +14,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use ffxiv_crafting::{Actions, Status};
+use ffxiv_crafting::{Actions, LimitedActionState, Status};
 
-use raphael_simulator::{Action, ActionMask, Settings, SimulationState};
-use raphael_solvers::{AtomicFlag, MacroSolver};
+use raphael_simulator::{Action, ActionMask, Combo, Settings, SimulationState};
+use raphael_solvers::MacroSolver;
 
+use super::{CancelToken, ProgressSink};
+
+/// 调用Raphael求解器，`status`可以是制作过程中的任意状态。
+/// 求解过程中找到的更优解和搜索进度会通知`sink`，被`cancel`中止时返回空的技能序列
 pub fn solve(
     status: Status,
     target_quality: Option<u32>,
@@ -29,6 +33,8 @@ pub fn solve(
     backload_progress: bool,
     adversarial: bool,
     unsound_branch_pruning: bool,
+    cancel: &CancelToken,
+    sink: &dyn ProgressSink,
 ) -> Vec<Actions> {
     let mut allowed_actions = ActionMask::all();
     if !use_heart_and_soul {
@@ -43,26 +49,32 @@ pub fn solve(
     if !use_trained_eye || status.is_action_allowed(Actions::TrainedEye).is_err() {
         allowed_actions = allowed_actions.remove(Action::TrainedEye);
     }
-    let target_quality = target_quality.unwrap_or(status.recipe.quality) as u16;
+    if status.is_finished() {
+        return Vec::new();
+    }
+    let target_quality = target_quality.unwrap_or(status.recipe.quality);
     let settings = Settings {
         max_cp: status.attributes.craft_points as i16,
         max_durability: status.recipe.durability as i8,
         max_progress: status.recipe.difficulty as u16,
-        max_quality: target_quality - status.quality as u16,
+        max_quality: target_quality.saturating_sub(status.quality) as u16,
         base_progress: status.caches.base_synth as u16,
         base_quality: status.caches.base_touch as u16,
         job_level: status.attributes.level,
         allowed_actions,
         adversarial,
     };
-    let state = SimulationState::new(&settings);
+    let state = simulation_state(&status, &settings);
     let mut solver = MacroSolver::new(
         settings,
         backload_progress,
         unsound_branch_pruning,
-        Box::new(|_| {}),
-        Box::new(|_| {}),
-        AtomicFlag::new(),
+        Box::new(|actions| {
+            let actions: Vec<_> = actions.iter().copied().map(map_action).collect();
+            sink.intermediate(&actions);
+        }),
+        Box::new(|progress| sink.progress(progress)),
+        cancel.flag(),
     );
     solver
         .solve(state)
@@ -72,6 +84,52 @@ pub fn solve(
         .collect()
 }
 
+/// 把制作中的状态转换为Raphael的模拟状态。
+/// 已获得的品质在`Settings::max_quality`中扣除，因此这里的品质总是从0开始。
+/// Raphael不模拟球色、最终确认和专家之证，这些状态会被忽略
+fn simulation_state(status: &Status, settings: &Settings) -> SimulationState {
+    let buffs = &status.buffs;
+    let combo = if status.step == 0 {
+        Combo::SynthesisBegin
+    } else if buffs.observed > 0 || buffs.touch_combo_stage == 2 {
+        // Raphael中观察与中级加工一样，都能让下一次的上级加工进入连击
+        Combo::StandardTouch
+    } else if buffs.touch_combo_stage == 1 {
+        Combo::BasicTouch
+    } else {
+        Combo::None
+    };
+    let mut state = SimulationState::new(settings);
+    state.cp = status.craft_points as i16;
+    state.durability = status.durability as i8;
+    state.progress = status.progress as u32;
+    state.effects = state
+        .effects
+        .with_inner_quiet(buffs.inner_quiet)
+        .with_waste_not(buffs.wast_not)
+        .with_innovation(buffs.innovation)
+        .with_veneration(buffs.veneration)
+        .with_great_strides(buffs.great_strides)
+        .with_muscle_memory(buffs.muscle_memory)
+        .with_manipulation(buffs.manipulation)
+        .with_trained_perfection_available(
+            buffs.trained_perfection == LimitedActionState::Unused
+                && settings.allowed_actions.has(Action::TrainedPerfection),
+        )
+        .with_trained_perfection_active(buffs.trained_perfection == LimitedActionState::Active)
+        .with_heart_and_soul_available(
+            buffs.heart_and_soul == LimitedActionState::Unused
+                && settings.allowed_actions.has(Action::HeartAndSoul),
+        )
+        .with_heart_and_soul_active(buffs.heart_and_soul == LimitedActionState::Active)
+        .with_quick_innovation_available(
+            buffs.quick_innovation_used == 0
+                && settings.allowed_actions.has(Action::QuickInnovation),
+        )
+        .with_combo(combo);
+    state
+}
+
 fn map_action(action: Action) -> Actions {
     match action {
         Action::BasicSynthesis => Actions::BasicSynthesis,
@@ -109,3 +167,68 @@ fn map_action(action: Action) -> Actions {
         Action::QuickInnovation => Actions::QuickInnovation,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::Actions;
+
+    use super::solve;
+    use crate::{solver::CancelToken, test_status};
+
+    /// 从`prefix`之后的状态开始求解，接上求解结果后从头模拟应当没有错误并推满进展
+    fn solve_after(prefix: &[Actions]) -> Vec<Actions> {
+        let init = test_status();
+        let mut status = init.clone();
+        for &action in prefix {
+            status.cast_action(action);
+        }
+        let actions = solve(
+            status,
+            Some(8000),
+            true,
+            true,
+            false,
+            false,
+            false,
+            false,
+            true,
+            &CancelToken::new(),
+            &(),
+        );
+        assert!(!actions.is_empty());
+        let full: Vec<_> = prefix
+            .iter()
+            .copied()
+            .chain(actions.iter().copied())
+            .collect();
+        let result = crate::simulate(init, full);
+        assert!(result.errors.is_empty());
+        assert!(result.status.progress >= result.status.recipe.difficulty);
+        actions
+    }
+
+    #[test]
+    fn after_observe() {
+        solve_after(&[Actions::Reflect, Actions::Observe]);
+    }
+
+    #[test]
+    fn with_manipulation_and_inner_quiet() {
+        solve_after(&[
+            Actions::Reflect,
+            Actions::Manipulation,
+            Actions::Innovation,
+            Actions::PreparatoryTouch,
+        ]);
+    }
+
+    #[test]
+    fn heart_and_soul_used() {
+        let actions = solve_after(&[
+            Actions::Reflect,
+            Actions::HeartAndSoul,
+            Actions::PreciseTouch,
+        ]);
+        assert!(!actions.contains(&Actions::HeartAndSoul));
+    }
+}
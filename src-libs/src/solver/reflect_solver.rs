@@ -16,26 +16,31 @@
 
 use crate::{
     simulate,
-    solver::{Score, Solver},
+    solver::{CancelToken, ProgressSink, Score, Solver},
     SimulateResult,
 };
 use ffxiv_crafting::{Actions, Buffs, Status};
 use micro_ndarray::Array;
 use std::cell::Cell;
 
+/// 被`cancel`中止时返回的结果没有意义，调用者应当丢弃
 pub fn solve(
     status: Status,
     use_manipulation: bool,
     use_waste_not: usize,
     use_observe: bool,
+    cancel: &CancelToken,
+    sink: &dyn ProgressSink,
 ) -> Vec<Actions> {
     let solver = QualitySolver::new(
         status.clone(),
         use_manipulation,
         use_waste_not + 1,
         use_observe,
+        cancel.clone(),
     );
     let result1 = solver.read_all(&status);
+    sink.intermediate(&result1);
     let SimulateResult { status: s1, .. } = simulate(status.clone(), result1.clone());
     // Try reflect
     let Ok(()) = status.is_action_allowed(Actions::Reflect) else {
@@ -104,16 +109,24 @@ pub struct QualitySolver {
     mn: bool,
     wn: usize,
     obz: bool,
+    cancel: CancelToken,
     // results [obz][iq][iv][gs][mn][wn][touch][tp][qi][d][cp]
     results: Array<Cell<SolverSlot<u32>>, 10>,
 }
 
 impl QualitySolver {
-    pub fn new(mut init_status: Status, mn: bool, wn: usize, obz: bool) -> Self {
+    /// 表格在求解时逐步填充，`cancel`被设置后会尽快返回。取消后表格中的结果不再可靠，求解器应当丢弃
+    pub fn new(
+        mut init_status: Status,
+        mn: bool,
+        wn: usize,
+        obz: bool,
+        cancel: CancelToken,
+    ) -> Self {
         init_status.progress = 0;
         let cp = init_status.attributes.craft_points as usize;
         let du = init_status.recipe.durability as usize;
-        let progress_solver = ProgressSolver::new(init_status, mn, wn, obz);
+        let progress_solver = ProgressSolver::new(init_status, mn, wn, obz, cancel.clone());
         let size = [
             obz as usize + 1,
             11,
@@ -141,6 +154,7 @@ impl QualitySolver {
             wn,
             mn,
             obz,
+            cancel,
             results,
         }
     }
@@ -174,6 +188,9 @@ impl QualitySolver {
                 return result;
             }
         }
+        if self.cancel.is_cancelled() {
+            return SolverSlot::default();
+        }
         if s.durability == 0 {
             let result = SolverSlot {
                 value: 0,
@@ -262,6 +279,9 @@ impl Solver for QualitySolver {
             (quality, step, action)
         };
         for cp in 0..=s.craft_points {
+            if self.cancel.is_cancelled() {
+                return None;
+            }
             new_s.craft_points = cp;
             for du in 1..=s.durability {
                 new_s.durability = du;
@@ -287,12 +307,13 @@ pub struct ProgressSolver {
     mn: bool,
     wn: usize,
     obz: bool,
+    cancel: CancelToken,
     // [obz][ve][mn][wn][tp][d][cp]
     results: Array<Cell<SolverSlot<u16>>, 7>,
 }
 
 impl ProgressSolver {
-    pub fn new(init_status: Status, mn: bool, wn: usize, obz: bool) -> Self {
+    pub fn new(init_status: Status, mn: bool, wn: usize, obz: bool, cancel: CancelToken) -> Self {
         let cp = init_status.attributes.craft_points as usize;
         let du = init_status.recipe.durability as usize;
         let size = [
@@ -317,6 +338,7 @@ impl ProgressSolver {
             mn,
             wn,
             obz,
+            cancel,
             results,
         }
     }
@@ -347,6 +369,9 @@ impl ProgressSolver {
                 return result;
             }
         }
+        if self.cancel.is_cancelled() {
+            return SolverSlot::default();
+        }
         if s.durability == 0 {
             let result = SolverSlot {
                 value: 0,
@@ -430,6 +455,9 @@ impl Solver for ProgressSolver {
             ..Buffs::default()
         };
         for cp in 0..=s.craft_points {
+            if self.cancel.is_cancelled() {
+                return None;
+            }
             new_s2.craft_points = cp;
             for du in 1..=s.durability {
                 new_s2.durability = du;
@@ -0,0 +1,273 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! 所有从一个状态求出完整技能序列的求解器
+//!
+//! 以下两类求解器的调用方式不同，不在注册表中：
+//!
+//! - [`super::expert_solver`]根据包含球色在内的当前状态只给出下一步的技能，
+//!   每步都需要用实际出现的球色重新调用，由各端的`expert_solve`单独提供。
+//! - [`super::muscle_memory_solver`]和[`super::reflect_solver::QualitySolver`]需要先建表，
+//!   之后可以从同一配方的任意状态反复读取结果，桌面端通过`create_solver`、`read_solver`
+//!   和`destroy_solver`管理建好的求解器。
+
+use ffxiv_crafting::{Actions, Status};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    depth_first_search_solver, normal_progress_solver, raphael, reflect_solver, rika_solver,
+    rika_tnze_solver, CancelToken, ProgressSink,
+};
+
+/// 求解器及其全部参数，序列化时以`name`区分求解器
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum SolverConfig {
+    Raphael {
+        /// 为`None`时以配方的最高品质为目标
+        target_quality: Option<u32>,
+        use_manipulation: bool,
+        use_heart_and_soul: bool,
+        use_quick_innovation: bool,
+        use_trained_eye: bool,
+        backload_progress: bool,
+        adversarial: bool,
+        unsound_branch_pruning: bool,
+    },
+    DepthFirstSearch {
+        depth: usize,
+        specialist: bool,
+    },
+    NormalProgress {
+        depth: usize,
+        specialist: bool,
+    },
+    Reflect {
+        use_manipulation: bool,
+        use_waste_not: usize,
+        use_observe: bool,
+    },
+    Rika,
+    RikaTnze {
+        use_manipulation: bool,
+        use_waste_not: usize,
+        use_observe: bool,
+        reduce_steps: bool,
+    },
+}
+
+/// 求解器优化的目标
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SolverTarget {
+    /// 在推满进展的前提下尽可能提高品质
+    MaxQuality,
+    /// 达到指定的品质即可
+    TargetQuality,
+    /// 只推满进展，不考虑品质
+    ProgressOnly,
+}
+
+/// 描述一个求解器的能力，供前端生成界面
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct SolverInfo {
+    /// 与[`SolverConfig`]序列化后的`name`相同
+    pub name: &'static str,
+    /// 桌面端是否可用
+    pub native: bool,
+    /// 网页版是否可用
+    pub wasm: bool,
+    /// 能否从制作中途的状态继续求解
+    pub mid_craft: bool,
+    pub targets: &'static [SolverTarget],
+    /// 可以通过参数禁止使用的技能
+    pub restrictable_actions: &'static [Actions],
+}
+
+/// 所有可用的求解器
+pub const SOLVERS: [SolverInfo; 6] = [
+    SolverInfo {
+        name: "raphael",
+        native: true,
+        wasm: true,
+        mid_craft: true,
+        targets: &[SolverTarget::MaxQuality, SolverTarget::TargetQuality],
+        restrictable_actions: &[
+            Actions::Manipulation,
+            Actions::HeartAndSoul,
+            Actions::QuickInnovation,
+            Actions::TrainedEye,
+        ],
+    },
+    SolverInfo {
+        name: "depth_first_search",
+        native: true,
+        wasm: true,
+        mid_craft: true,
+        targets: &[SolverTarget::MaxQuality],
+        restrictable_actions: &[Actions::HeartAndSoul],
+    },
+    SolverInfo {
+        name: "normal_progress",
+        native: true,
+        wasm: true,
+        mid_craft: true,
+        targets: &[SolverTarget::ProgressOnly],
+        restrictable_actions: &[Actions::HeartAndSoul],
+    },
+    SolverInfo {
+        name: "reflect",
+        native: true,
+        wasm: true,
+        mid_craft: true,
+        targets: &[SolverTarget::MaxQuality],
+        restrictable_actions: &[
+            Actions::Manipulation,
+            Actions::WasteNot,
+            Actions::WasteNotII,
+            Actions::Observe,
+        ],
+    },
+    SolverInfo {
+        name: "rika",
+        native: true,
+        wasm: true,
+        mid_craft: false,
+        targets: &[SolverTarget::MaxQuality],
+        restrictable_actions: &[],
+    },
+    // 占用内存过多，不在网页版中提供
+    SolverInfo {
+        name: "rika_tnze",
+        native: true,
+        wasm: false,
+        mid_craft: false,
+        targets: &[SolverTarget::MaxQuality],
+        restrictable_actions: &[
+            Actions::Manipulation,
+            Actions::WasteNot,
+            Actions::WasteNotII,
+            Actions::Observe,
+        ],
+    },
+];
+
+impl SolverConfig {
+    pub fn info(&self) -> &'static SolverInfo {
+        let name = match self {
+            SolverConfig::Raphael { .. } => "raphael",
+            SolverConfig::DepthFirstSearch { .. } => "depth_first_search",
+            SolverConfig::NormalProgress { .. } => "normal_progress",
+            SolverConfig::Reflect { .. } => "reflect",
+            SolverConfig::Rika => "rika",
+            SolverConfig::RikaTnze { .. } => "rika_tnze",
+        };
+        SOLVERS.iter().find(|info| info.name == name).unwrap()
+    }
+
+    /// 为支持目标品质的求解器设置目标品质，其他求解器不受影响
+    pub fn with_target_quality(mut self, quality: u32) -> Self {
+        if let SolverConfig::Raphael { target_quality, .. } = &mut self {
+            *target_quality = Some(quality);
+        }
+        self
+    }
+
+    /// 检查求解器能否在当前平台上从`status`开始求解
+    pub fn check(&self, status: &Status) -> Result<(), String> {
+        let info = self.info();
+        let available = if cfg!(target_family = "wasm") {
+            info.wasm
+        } else {
+            info.native
+        };
+        if !available {
+            return Err(String::from("solver-unavailable"));
+        }
+        if status.step > 0 && !info.mid_craft {
+            return Err(String::from("solver-doesn-t-support-mid-craft"));
+        }
+        Ok(())
+    }
+}
+
+/// 所有求解器的统一入口。
+/// 各求解器对`cancel`和`sink`的支持程度不同，被中止时返回的结果没有意义
+pub fn solve(
+    config: &SolverConfig,
+    status: Status,
+    cancel: &CancelToken,
+    sink: &dyn ProgressSink,
+) -> Result<Vec<Actions>, String> {
+    config.check(&status)?;
+    let actions = match *config {
+        SolverConfig::Raphael {
+            target_quality,
+            use_manipulation,
+            use_heart_and_soul,
+            use_quick_innovation,
+            use_trained_eye,
+            backload_progress,
+            adversarial,
+            unsound_branch_pruning,
+        } => raphael::solve(
+            status,
+            target_quality,
+            use_manipulation,
+            use_heart_and_soul,
+            use_quick_innovation,
+            use_trained_eye,
+            backload_progress,
+            adversarial,
+            unsound_branch_pruning,
+            cancel,
+            sink,
+        ),
+        SolverConfig::DepthFirstSearch { depth, specialist } => {
+            depth_first_search_solver::solve(status, depth, specialist, cancel, sink)
+        }
+        SolverConfig::NormalProgress { depth, specialist } => {
+            normal_progress_solver::solve(status, depth, specialist, cancel)
+        }
+        SolverConfig::Reflect {
+            use_manipulation,
+            use_waste_not,
+            use_observe,
+        } => reflect_solver::solve(
+            status,
+            use_manipulation,
+            use_waste_not,
+            use_observe,
+            cancel,
+            sink,
+        ),
+        SolverConfig::Rika => rika_solver::solve(status, cancel),
+        SolverConfig::RikaTnze {
+            use_manipulation,
+            use_waste_not,
+            use_observe,
+            reduce_steps,
+        } => rika_tnze_solver::solve(
+            status,
+            use_manipulation,
+            use_waste_not,
+            use_observe,
+            reduce_steps,
+            cancel,
+        ),
+    };
+    Ok(actions)
+}
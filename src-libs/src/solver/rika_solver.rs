@@ -18,11 +18,17 @@
 use ffxiv_crafting::{Actions, Buffs, Status};
 use std::collections::VecDeque;
 
-pub fn solve(craft: Status) -> Vec<Actions> {
+use super::CancelToken;
+
+/// 被`cancel`中止时返回的结果没有意义
+pub fn solve(craft: Status, cancel: &CancelToken) -> Vec<Actions> {
     let phase1_routes = generate_routes_phase1(craft);
     let mut phase2_routes = Vec::new();
     for route in phase1_routes {
-        if let Some(route) = generate_routes_phase2(route) {
+        if cancel.is_cancelled() {
+            break;
+        }
+        if let Some(route) = generate_routes_phase2(route, cancel) {
             phase2_routes.push(route);
         }
     }
@@ -199,11 +205,15 @@ pub fn next_action_phase_2(craft: &Status) -> Vec<Actions> {
 
 pub fn generate_routes_phase2(
     (craft, route): (Status, Vec<Actions>),
+    cancel: &CancelToken,
 ) -> Option<(Status, Vec<Actions>)> {
     let mut queue = VecDeque::new();
     queue.push_back((craft, route));
     let mut top_route: Option<(Status, Vec<Actions>)> = None;
     while let Some((_craft, _route)) = queue.pop_front() {
+        if cancel.is_cancelled() {
+            return None;
+        }
         for action in next_action_phase_2(&_craft) {
             if _craft.is_finished() || _craft.is_action_allowed(action).is_err() {
                 continue;
@@ -230,32 +240,20 @@ pub fn generate_routes_phase2(
 
 #[cfg(test)]
 mod test {
-    use ffxiv_crafting::{data::recipe_level_table, Attributes, Recipe, Status};
-
     use super::solve;
-
-    fn init() -> Status {
-        let r = Recipe {
-            rlv: recipe_level_table(620),
-            job_level: 90,
-            difficulty: 5720,
-            quality: 12900,
-            durability: 70,
-            conditions_flag: 15,
-        };
-        let a = Attributes {
-            level: 90,
-            craftsmanship: 4214,
-            control: 3528,
-            craft_points: 691,
-        };
-        Status::new(a, r)
-    }
+    use crate::{solver::CancelToken, test_status};
 
     #[test]
     fn test() {
-        let init_status = init();
-        let result = solve(init_status);
+        let init_status = test_status();
+        let result = solve(init_status, &CancelToken::new());
         println!("{result:?}");
     }
+
+    #[test]
+    fn cancelled() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert!(solve(test_status(), &cancel).is_empty());
+    }
 }
@@ -14,15 +14,21 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use app_libs::{
-    ffxiv_crafting::{Actions, Status},
-    solver::Score,
-};
+use ffxiv_crafting::{Actions, Status};
 
-use crate::memoization_solver;
+use super::{memoization_solver, CancelToken, Score};
 
-pub fn solve(craft: Status, mn: bool, wn: usize, obz: bool, reduce_steps: bool) -> Vec<Actions> {
-    let tnzes_quality_solver = memoization_solver::Solver::new(craft.clone(), mn, wn, obz);
+/// 被`cancel`中止时尽快返回，此时的结果没有意义
+pub fn solve(
+    craft: Status,
+    mn: bool,
+    wn: usize,
+    obz: bool,
+    reduce_steps: bool,
+    cancel: &CancelToken,
+) -> Vec<Actions> {
+    let tnzes_quality_solver =
+        memoization_solver::Solver::new(craft.clone(), mn, wn, obz, cancel.clone());
     let phase1_routes = generate_routes_phase1(&craft, mn);
     let mut phase2_routes = Vec::new();
     let basic_prog = craft.calc_synthesis(match craft.attributes.level < 31 {
@@ -35,6 +41,9 @@ pub fn solve(craft: Status, mn: bool, wn: usize, obz: bool, reduce_steps: bool)
     });
 
     for (s, actions) in phase1_routes {
+        if cancel.is_cancelled() {
+            break;
+        }
         let (final_actions, final_cp) = match s.recipe.difficulty - s.progress {
             x if x <= basic_prog => (vec![Actions::BasicSynthesis], 0),
             x if x <= careful_prog => (vec![Actions::CarefulSynthesis], 7),
@@ -0,0 +1,330 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ffxiv_crafting::{Actions, Buffs, CastActionError, Condition, LimitedActionState, Status};
+use serde::{Deserialize, Serialize};
+
+/// 会在时间轴上显示的增益效果
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuffKind {
+    MuscleMemory,
+    GreatStrides,
+    Veneration,
+    Innovation,
+    FinalAppraisal,
+    Manipulation,
+    WasteNot,
+    Expedience,
+    HeartAndSoul,
+    TrainedPerfection,
+}
+
+/// 技能序列中一个技能的模拟记录
+#[derive(Serialize)]
+pub struct TraceStep {
+    pub pos: usize,
+    pub action: Actions,
+    /// 执行该技能后的状态，技能被跳过时与执行前相同
+    pub status: Status,
+    pub progress_delta: i32,
+    pub quality_delta: i32,
+    pub durability_delta: i32,
+    pub craft_points_delta: i32,
+    /// 新获得或被刷新持续时间的增益
+    pub buffs_started: Vec<BuffKind>,
+    /// 持续时间自然耗尽的增益
+    pub buffs_expired: Vec<BuffKind>,
+    /// 被技能提前消耗掉的增益
+    pub buffs_consumed: Vec<BuffKind>,
+    /// 技能不满足释放条件而被跳过，原因见`error`
+    pub skipped: bool,
+    pub error: Option<CastActionError>,
+}
+
+#[derive(Serialize)]
+pub struct SimulateTraceResult {
+    pub status: Status,
+    pub steps: Vec<TraceStep>,
+}
+
+/// 与[`crate::simulate`]相同地按顺序模拟技能序列，但额外记录每个技能执行前后的变化，
+/// 用于绘制制作过程的时间轴
+pub fn simulate_trace(mut status: Status, actions: Vec<Actions>) -> SimulateTraceResult {
+    let mut steps = Vec::with_capacity(actions.len());
+    for (pos, action) in actions.into_iter().enumerate() {
+        let before = status.clone();
+        let error = status.is_action_allowed(action).err();
+        if error.is_none() {
+            status.cast_action(action);
+        }
+        steps.push(trace_step(pos, action, &before, &status, error));
+    }
+    SimulateTraceResult { status, steps }
+}
+
+/// 实际制作中的一步，用于按游戏内的真实过程重放一次制作
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct ScriptedStep {
+    pub action: Actions,
+    /// 释放该技能时的球色
+    pub condition: Condition,
+    /// 技能是否成功，只对坯料加工、仓促和冒进有效，缺省视为成功
+    #[serde(default)]
+    pub success: Option<bool>,
+}
+
+/// 按给定的球色序列和技能成败重放一次制作，
+/// 返回值与[`simulate_trace`]相同，失败的技能在记录中以对应的失败技能表示。
+/// 为不会失败的技能指定失败时返回错误
+pub fn simulate_scripted(
+    mut status: Status,
+    script: Vec<ScriptedStep>,
+) -> Result<SimulateTraceResult, String> {
+    let mut steps = Vec::with_capacity(script.len());
+    for (pos, step) in script.into_iter().enumerate() {
+        status.condition = step.condition;
+        let before = status.clone();
+        let action = match (step.action, step.success) {
+            (Actions::RapidSynthesis, Some(false)) => Actions::RapidSynthesisFail,
+            (Actions::HastyTouch, Some(false)) => Actions::HastyTouchFail,
+            (Actions::DaringTouch, Some(false)) => Actions::DaringTouchFail,
+            (_, Some(false)) => return Err(format!("action-cannot-fail: {pos}")),
+            (action, _) => action,
+        };
+        // 失败的技能与成功时的释放条件相同，因此总是按原技能检查
+        let error = status.is_action_allowed(step.action).err();
+        if error.is_none() {
+            status.cast_action(action);
+        }
+        steps.push(trace_step(pos, action, &before, &status, error));
+    }
+    Ok(SimulateTraceResult { status, steps })
+}
+
+/// 比较技能执行前后的状态，生成一条模拟记录
+pub(crate) fn trace_step(
+    pos: usize,
+    action: Actions,
+    before: &Status,
+    after: &Status,
+    error: Option<CastActionError>,
+) -> TraceStep {
+    let mut step = TraceStep {
+        pos,
+        action,
+        status: after.clone(),
+        progress_delta: after.progress as i32 - before.progress as i32,
+        quality_delta: after.quality as i32 - before.quality as i32,
+        durability_delta: after.durability as i32 - before.durability as i32,
+        craft_points_delta: after.craft_points - before.craft_points,
+        buffs_started: Vec::new(),
+        buffs_expired: Vec::new(),
+        buffs_consumed: Vec::new(),
+        skipped: error.is_some(),
+        error,
+    };
+    diff_buffs(&before.buffs, &after.buffs, &mut step);
+    step
+}
+
+fn diff_buffs(before: &Buffs, after: &Buffs, step: &mut TraceStep) {
+    let timed = [
+        (
+            BuffKind::MuscleMemory,
+            before.muscle_memory,
+            after.muscle_memory,
+        ),
+        (
+            BuffKind::GreatStrides,
+            before.great_strides,
+            after.great_strides,
+        ),
+        (BuffKind::Veneration, before.veneration, after.veneration),
+        (BuffKind::Innovation, before.innovation, after.innovation),
+        (
+            BuffKind::FinalAppraisal,
+            before.final_appraisal,
+            after.final_appraisal,
+        ),
+        (
+            BuffKind::Manipulation,
+            before.manipulation,
+            after.manipulation,
+        ),
+        (BuffKind::WasteNot, before.wast_not, after.wast_not),
+        (BuffKind::Expedience, before.expedience, after.expedience),
+    ];
+    for (kind, before, after) in timed {
+        if after > before {
+            step.buffs_started.push(kind);
+        } else if before > 0 && after == 0 {
+            // 只剩一回合的增益本来就会在这一步结束，除非它确实生效了，否则视为自然到期
+            let used = match kind {
+                BuffKind::MuscleMemory => step.progress_delta > 0,
+                BuffKind::GreatStrides => step.quality_delta > 0,
+                _ => false,
+            };
+            if before > 1 || used {
+                step.buffs_consumed.push(kind);
+            } else {
+                step.buffs_expired.push(kind);
+            }
+        }
+    }
+
+    let limited = [
+        (
+            BuffKind::HeartAndSoul,
+            before.heart_and_soul,
+            after.heart_and_soul,
+        ),
+        (
+            BuffKind::TrainedPerfection,
+            before.trained_perfection,
+            after.trained_perfection,
+        ),
+    ];
+    for (kind, before, after) in limited {
+        match (before, after) {
+            (LimitedActionState::Unused, LimitedActionState::Active) => {
+                step.buffs_started.push(kind)
+            }
+            (LimitedActionState::Active, LimitedActionState::Used) => {
+                step.buffs_consumed.push(kind)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ffxiv_crafting::{Actions, Condition};
+
+    use super::{simulate_scripted, simulate_trace, BuffKind, ScriptedStep};
+    use crate::test_status;
+
+    #[test]
+    fn trace_deltas_and_buffs() {
+        let init_status = test_status();
+        let actions = vec![
+            Actions::MuscleMemory,
+            Actions::Veneration,
+            Actions::Groundwork,
+            Actions::GreatStrides,
+            Actions::BasicTouch,
+            // 坚信只能在第一步使用，会被跳过
+            Actions::MuscleMemory,
+        ];
+        let result = simulate_trace(init_status.clone(), actions);
+        let steps = &result.steps;
+        assert_eq!(steps.len(), 6);
+
+        let sum = |f: fn(&super::TraceStep) -> i32| steps.iter().map(f).sum::<i32>();
+        assert_eq!(sum(|s| s.progress_delta), result.status.progress as i32);
+        assert_eq!(sum(|s| s.quality_delta), result.status.quality as i32);
+        assert_eq!(
+            init_status.craft_points + sum(|s| s.craft_points_delta),
+            result.status.craft_points
+        );
+        assert_eq!(
+            init_status.durability as i32 + sum(|s| s.durability_delta),
+            result.status.durability as i32
+        );
+
+        let costs: Vec<_> = steps
+            .iter()
+            .map(|s| (s.craft_points_delta, s.durability_delta))
+            .collect();
+        assert_eq!(
+            costs,
+            [
+                (-6, -10),
+                (-18, 0),
+                (-18, -20),
+                (-32, 0),
+                (-18, -10),
+                (0, 0)
+            ]
+        );
+
+        assert!(steps[0].progress_delta > 0);
+        assert_eq!(steps[0].buffs_started, [BuffKind::MuscleMemory]);
+        assert_eq!(steps[1].progress_delta, 0);
+        assert_eq!(steps[1].buffs_started, [BuffKind::Veneration]);
+        assert!(steps[2].progress_delta > steps[0].progress_delta);
+        assert_eq!(steps[2].buffs_consumed, [BuffKind::MuscleMemory]);
+        assert_eq!(steps[3].buffs_started, [BuffKind::GreatStrides]);
+        assert!(steps[4].quality_delta > 0);
+        assert_eq!(steps[4].buffs_consumed, [BuffKind::GreatStrides]);
+
+        assert!(steps[..5].iter().all(|s| !s.skipped));
+        assert!(steps[5].skipped && steps[5].error.is_some());
+        assert_eq!(steps[5].status.step, steps[4].status.step);
+    }
+
+    fn scripted(action: Actions, condition: Condition, success: Option<bool>) -> ScriptedStep {
+        ScriptedStep {
+            action,
+            condition,
+            success,
+        }
+    }
+
+    #[test]
+    fn scripted_success_and_failure() {
+        let script = vec![
+            scripted(Actions::RapidSynthesis, Condition::Normal, Some(false)),
+            scripted(Actions::RapidSynthesis, Condition::Normal, Some(true)),
+            scripted(Actions::HastyTouch, Condition::Normal, None),
+        ];
+        let result = simulate_scripted(test_status(), script).unwrap();
+        let steps = &result.steps;
+        assert_eq!(steps[0].action, Actions::RapidSynthesisFail);
+        assert_eq!(steps[0].progress_delta, 0);
+        assert_eq!(steps[0].durability_delta, -10);
+        assert_eq!(steps[1].action, Actions::RapidSynthesis);
+        assert!(steps[1].progress_delta > 0);
+        // 未指定成败时视为成功
+        assert_eq!(steps[2].action, Actions::HastyTouch);
+        assert!(steps[2].quality_delta > 0);
+    }
+
+    #[test]
+    fn scripted_conditions() {
+        let touch = |condition| {
+            let script = vec![scripted(Actions::BasicTouch, condition, None)];
+            simulate_scripted(test_status(), script).unwrap().steps[0].quality_delta
+        };
+        let normal = touch(Condition::Normal);
+        assert!(normal > 0);
+        assert!(touch(Condition::Poor) < normal);
+        assert!(touch(Condition::Good) > normal);
+        assert!(touch(Condition::Excellent) > touch(Condition::Good));
+    }
+
+    #[test]
+    fn scripted_failure_of_infallible_action() {
+        let script = vec![
+            scripted(Actions::Veneration, Condition::Normal, Some(true)),
+            scripted(Actions::BasicTouch, Condition::Normal, Some(false)),
+        ];
+        assert_eq!(
+            simulate_scripted(test_status(), script).err().as_deref(),
+            Some("action-cannot-fail: 1")
+        );
+    }
+}
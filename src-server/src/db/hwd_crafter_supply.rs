@@ -0,0 +1,32 @@
+//! 伊修加德重建的交纳物品，游戏中HWDCrafterSupply表的每一行按`ItemTradeIn`展开为多行
+
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "HWDCrafterSupply")]
+pub struct Model {
+    #[sea_orm(column_name = "Id", primary_key, auto_increment = false)]
+    pub id: u32,
+    #[sea_orm(column_name = "ItemTradeInId", primary_key, auto_increment = false)]
+    pub item_trade_in_id: u32,
+    #[sea_orm(column_name = "Level")]
+    pub level: u16,
+    #[sea_orm(column_name = "BaseCollectableRating")]
+    pub base_collectable_rating: u16,
+    #[sea_orm(column_name = "MidCollectableRating")]
+    pub mid_collectable_rating: u16,
+    #[sea_orm(column_name = "HighCollectableRating")]
+    pub high_collectable_rating: u16,
+    #[sea_orm(column_name = "BaseCollectableRewardId")]
+    pub base_collectable_reward_id: u32,
+    #[sea_orm(column_name = "MidCollectableRewardId")]
+    pub mid_collectable_reward_id: u32,
+    #[sea_orm(column_name = "HighCollectableRewardId")]
+    pub high_collectable_reward_id: u32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
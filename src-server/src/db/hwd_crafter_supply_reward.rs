@@ -0,0 +1,22 @@
+//! 伊修加德重建交纳的奖励，对应游戏中的HWDCrafterSupplyReward表
+
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "HWDCrafterSupplyReward")]
+pub struct Model {
+    #[sea_orm(column_name = "Id", primary_key)]
+    pub id: u32,
+    #[sea_orm(column_name = "ScriptRewardAmount")]
+    pub script_reward_amount: u32,
+    #[sea_orm(column_name = "ExpReward")]
+    pub exp_reward: u32,
+    #[sea_orm(column_name = "Points")]
+    pub points: u32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,24 @@
+//! 萨雷安的交纳物品，游戏中SharlayanCraftWorkSupply表的每一行按`ItemId`展开为多行
+
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "SharlayanCraftWorkSupply")]
+pub struct Model {
+    #[sea_orm(column_name = "Id", primary_key, auto_increment = false)]
+    pub id: u32,
+    #[sea_orm(column_name = "ItemId", primary_key, auto_increment = false)]
+    pub item_id: u32,
+    #[sea_orm(column_name = "Level")]
+    pub level: u16,
+    #[sea_orm(column_name = "CollectabilityMid")]
+    pub collectability_mid: u16,
+    #[sea_orm(column_name = "CollectabilityHigh")]
+    pub collectability_high: u16,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
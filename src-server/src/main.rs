@@ -16,18 +16,32 @@
 
 use std::{collections::BTreeMap, env};
 
+use app_libs::{
+    analyzer::rand_simulations::{
+        CollectablesMetadata, CollectablesMetadataKind, CollectablesMetadataRow,
+    },
+    bom::{self, BomItem, BomRecipe},
+    enhancer::Enhancer,
+    ffxiv_crafting::RecipeLevel,
+    recipe::RecipeFilter,
+};
 use salvo::cors;
 use salvo::cors::Cors;
 use salvo::hyper::Method;
 use salvo::prelude::*;
-use sea_orm::{entity::*, query::*, Database, DatabaseConnection, FromQueryResult};
+use sea_orm::{
+    entity::*, query::*, sea_query::Query, Database, DatabaseConnection, DbErr, FromQueryResult,
+    SelectModel, Selector,
+};
 use serde::Serialize;
 
 mod db;
+mod recipe_filter;
 use db::{
-    craft_types, item_action, item_food, item_food_effect, item_with_amount, items, prelude::*,
-    recipe_level_tables, recipes,
+    craft_types, hwd_crafter_supply_reward, item_action, item_food, item_food_effect,
+    item_with_amount, items, prelude::*, recipe_level_tables, recipes, satisfaction_supply,
 };
+use recipe_filter::ApplyRecipeFilter;
 
 type Result<T> = std::result::Result<T, StatusError>;
 
@@ -66,7 +80,10 @@ async fn main() {
         .push(Router::with_path("recipe_level_table").get(recipe_level_table))
         .push(Router::with_path("recipe_table").get(recipe_table))
         .push(Router::with_path("recipe_info").get(recipe_info))
+        .push(Router::with_path("recipe").get(recipe))
         .push(Router::with_path("recipes_ingredientions").get(recipes_ingredientions))
+        .push(Router::with_path("recipes_using_item").get(recipes_using_item))
+        .push(Router::with_path("bom").get(bill_of_materials))
         .push(Router::with_path("recipe_collectability").get(recipe_collectability))
         .push(Router::with_path("item_info").get(item_info))
         .push(Router::with_path("craft_type").get(craft_type))
@@ -97,6 +114,32 @@ struct RecipeInfo {
     can_hq: bool,
 }
 
+/// 选出构造RecipeInfo所需的列，`query`需要已经连接了物品和职业表
+fn select_recipe_info(query: Select<Recipes>) -> Selector<SelectModel<RecipeInfo>> {
+    query
+        .select_only()
+        .column_as(recipes::Column::Id, "id")
+        .column_as(recipes::Column::RecipeLevelId, "rlv")
+        .column_as(items::Column::Id, "item_id")
+        .column_as(items::Column::Name, "item_name")
+        .column_as(item_with_amount::Column::Amount, "item_amount")
+        .column_as(craft_types::Column::Name, "job")
+        .column_as(recipes::Column::DifficultyFactor, "difficulty_factor")
+        .column_as(recipes::Column::QualityFactor, "quality_factor")
+        .column_as(recipes::Column::DurabilityFactor, "durability_factor")
+        .column_as(
+            recipes::Column::MaterialQualityFactor,
+            "material_quality_factor",
+        )
+        .column_as(
+            recipes::Column::RequiredCraftsmanship,
+            "required_craftsmanship",
+        )
+        .column_as(recipes::Column::RequiredControl, "required_control")
+        .column_as(recipes::Column::CanHq, "can_hq")
+        .into_model::<RecipeInfo>()
+}
+
 // rlv: i32,
 #[handler]
 async fn recipe_level_table(
@@ -143,7 +186,6 @@ async fn recipe_table(req: &mut Request, depot: &mut Depot, res: &mut Response)
             recipes::Relation::RecipeLevelTables.def(),
         )
         .join(JoinType::InnerJoin, item_with_amount::Relation::Items.def())
-        .select_only()
         .filter(items::Column::Name.like(&search_name));
     if let Some(rlv) = req.query::<u32>("rlv") {
         query = query.filter(recipes::Column::RecipeLevelId.eq(rlv))
@@ -157,28 +199,10 @@ async fn recipe_table(req: &mut Request, depot: &mut Depot, res: &mut Response)
     if let Some(job_level_max) = req.query::<u32>("job_level_max") {
         query = query.filter(recipe_level_tables::Column::ClassJobLevel.lte(job_level_max))
     }
-    let query = query
-        .column_as(recipes::Column::Id, "id")
-        .column_as(recipes::Column::RecipeLevelId, "rlv")
-        .column_as(items::Column::Id, "item_id")
-        .column_as(items::Column::Name, "item_name")
-        .column_as(item_with_amount::Column::Amount, "item_amount")
-        .column_as(craft_types::Column::Name, "job")
-        .column_as(recipes::Column::DifficultyFactor, "difficulty_factor")
-        .column_as(recipes::Column::QualityFactor, "quality_factor")
-        .column_as(recipes::Column::DurabilityFactor, "durability_factor")
-        .column_as(
-            recipes::Column::MaterialQualityFactor,
-            "material_quality_factor",
-        )
-        .column_as(
-            recipes::Column::RequiredCraftsmanship,
-            "required_craftsmanship",
-        )
-        .column_as(recipes::Column::RequiredControl, "required_control")
-        .column_as(recipes::Column::CanHq, "can_hq")
-        .order_by(recipes::Column::Id, Order::Asc);
-    let paginate = query.into_model::<RecipeInfo>().paginate(&state.conn, 200);
+    let filter: RecipeFilter<u32> = req
+        .parse_queries()
+        .map_err(|_| StatusError::bad_request().detail("Invalid filter"))?;
+    let paginate = select_recipe_info(filter.apply(query)).paginate(&state.conn, 200);
 
     let p = paginate.num_pages().await.map_err(|err| {
         println!("Failed to get total page numbers: {err}");
@@ -258,7 +282,57 @@ async fn recipe_info(req: &mut Request, depot: &mut Depot, res: &mut Response) -
     let recipe_id = req
         .query::<u32>("recipe_id")
         .ok_or_else(|| StatusError::bad_request().detail("Need 'recipe_id'"))?;
-    let result = Recipes::find_by_id(recipe_id)
+    let query = Recipes::find_by_id(recipe_id)
+        .join(JoinType::InnerJoin, recipes::Relation::CraftTypes.def())
+        .join(JoinType::InnerJoin, recipes::Relation::ItemWithAmount.def())
+        .join(
+            JoinType::InnerJoin,
+            recipes::Relation::RecipeLevelTables.def(),
+        )
+        .join(JoinType::InnerJoin, item_with_amount::Relation::Items.def());
+    let result = select_recipe_info(query)
+        .one(&state.conn)
+        .await
+        .map_err(|e| {
+            println!("recipe_info error: {e:?}");
+            StatusError::internal_server_error()
+        })?
+        .ok_or_else(|| StatusError::bad_request().detail("Recipe not found"))?;
+    res.render(Json(result));
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct IngredientUsage {
+    #[serde(flatten)]
+    recipe: RecipeInfo,
+    /// 每次制作消耗该物品的数量
+    amount: i32,
+}
+
+/// 分页查询使用了指定物品作为素材的所有配方
+#[handler]
+async fn recipes_using_item(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> Result<()> {
+    let state = depot
+        .obtain::<AppState>()
+        .map_err(|_| StatusError::internal_server_error().detail("Obtain AppState error"))?;
+    let item_id = req
+        .query::<u32>("item_id")
+        .ok_or_else(|| StatusError::bad_request().detail("Need 'item_id'"))?;
+    let page_id = req
+        .query::<u64>("page_id")
+        .ok_or_else(|| StatusError::bad_request().detail("Need 'page_id'"))?;
+
+    let used_in = Query::select()
+        .column(item_with_amount::Column::RecipeId)
+        .from(ItemWithAmount)
+        .and_where(item_with_amount::Column::IngredientId.eq(item_id))
+        .to_owned();
+    let query = Recipes::find()
         .join(JoinType::InnerJoin, recipes::Relation::CraftTypes.def())
         .join(JoinType::InnerJoin, recipes::Relation::ItemWithAmount.def())
         .join(
@@ -266,38 +340,171 @@ async fn recipe_info(req: &mut Request, depot: &mut Depot, res: &mut Response) -
             recipes::Relation::RecipeLevelTables.def(),
         )
         .join(JoinType::InnerJoin, item_with_amount::Relation::Items.def())
+        .filter(recipes::Column::Id.in_subquery(used_in))
+        .order_by(recipes::Column::Id, Order::Asc);
+    let paginate = select_recipe_info(query).paginate(&state.conn, 200);
+    let p = paginate.num_pages().await.map_err(|err| {
+        println!("Failed to get total page numbers: {err}");
+        StatusError::internal_server_error().detail("Failed to get total page numbers")
+    })?;
+    let recipes = paginate.fetch_page(page_id).await.map_err(|err| {
+        println!("Failed to get recipe data: {err}");
+        StatusError::internal_server_error().detail("Failed to get recipe data")
+    })?;
+
+    let mut amounts = BTreeMap::new();
+    let ingredients = ItemWithAmount::find()
+        .filter(item_with_amount::Column::IngredientId.eq(item_id))
+        .filter(item_with_amount::Column::RecipeId.is_in(recipes.iter().map(|r| r.id)))
+        .all(&state.conn)
+        .await
+        .map_err(|_| StatusError::internal_server_error())?;
+    for v in ingredients {
+        *amounts.entry(v.recipe_id).or_insert(0) += v.amount;
+    }
+    let data: Vec<_> = recipes
+        .into_iter()
+        .map(|recipe| IngredientUsage {
+            amount: amounts.get(&Some(recipe.id)).copied().unwrap_or(0),
+            recipe,
+        })
+        .collect();
+
+    #[derive(Serialize)]
+    struct Resp {
+        data: Vec<IngredientUsage>,
+        p: u64,
+    }
+    res.render(Json(Resp { data, p }));
+    Ok(())
+}
+
+#[derive(FromQueryResult)]
+struct BomRecipeRow {
+    id: u32,
+    job: String,
+    item_amount: i32,
+}
+
+/// 查询物品的名称以及制作它的配方，有多个配方时使用ID最小的一个
+async fn bom_item(db: &DatabaseConnection, item_id: u32) -> std::result::Result<BomItem, DbErr> {
+    let name = Items::find_by_id(item_id)
+        .one(db)
+        .await?
+        .map(|v| v.name)
+        .unwrap_or_default();
+    let Some(r) = Recipes::find()
+        .join(JoinType::InnerJoin, recipes::Relation::CraftTypes.def())
+        .join(JoinType::InnerJoin, recipes::Relation::ItemWithAmount.def())
+        .filter(item_with_amount::Column::IngredientId.eq(item_id))
+        .order_by_asc(recipes::Column::Id)
         .select_only()
         .column_as(recipes::Column::Id, "id")
-        .column_as(recipes::Column::RecipeLevelId, "rlv")
-        .column_as(items::Column::Id, "item_id")
-        .column_as(items::Column::Name, "item_name")
-        .column_as(item_with_amount::Column::Amount, "item_amount")
         .column_as(craft_types::Column::Name, "job")
-        .column_as(recipes::Column::DifficultyFactor, "difficulty_factor")
-        .column_as(recipes::Column::QualityFactor, "quality_factor")
-        .column_as(recipes::Column::DurabilityFactor, "durability_factor")
-        .column_as(
-            recipes::Column::MaterialQualityFactor,
-            "material_quality_factor",
-        )
-        .column_as(
-            recipes::Column::RequiredCraftsmanship,
-            "required_craftsmanship",
-        )
-        .column_as(recipes::Column::RequiredControl, "required_control")
-        .column_as(recipes::Column::CanHq, "can_hq")
-        .into_model::<RecipeInfo>()
+        .column_as(item_with_amount::Column::Amount, "item_amount")
+        .into_model::<BomRecipeRow>()
+        .one(db)
+        .await?
+    else {
+        return Ok(BomItem { name, recipe: None });
+    };
+    let ingredients = ItemWithAmount::find()
+        .filter(item_with_amount::Column::RecipeId.eq(r.id))
+        .all(db)
+        .await?;
+    Ok(BomItem {
+        name,
+        recipe: Some(BomRecipe {
+            recipe_id: r.id,
+            job: r.job,
+            item_amount: r.item_amount as u32,
+            ingredients: ingredients
+                .into_iter()
+                .map(|v| (v.ingredient_id, v.amount as u32))
+                .collect(),
+        }),
+    })
+}
+
+/// 递归展开制作目标物品所需的全部半成品和原材料，
+/// 目标物品以`targets=item_id:amount,item_id:amount`的形式传入
+#[handler]
+async fn bill_of_materials(req: &mut Request, depot: &mut Depot, res: &mut Response) -> Result<()> {
+    let state = depot
+        .obtain::<AppState>()
+        .map_err(|_| StatusError::internal_server_error().detail("Obtain AppState error"))?;
+    let targets = req
+        .query::<String>("targets")
+        .ok_or_else(|| StatusError::bad_request().detail("Need 'targets'"))?;
+    let targets = targets
+        .split(',')
+        .map(|target| {
+            let (id, amount) = target.split_once(':')?;
+            Some((id.parse().ok()?, amount.parse().ok()?))
+        })
+        .collect::<Option<Vec<(u32, u32)>>>()
+        .ok_or_else(|| StatusError::bad_request().detail("Invalid 'targets'"))?;
+    let items = bom::collect_items(&targets, |id| bom_item(&state.conn, id))
+        .await
+        .map_err(|e| {
+            println!("bom error: {e:?}");
+            StatusError::internal_server_error()
+        })?;
+    let result =
+        bom::resolve(&targets, &items).map_err(|e| StatusError::bad_request().detail(e))?;
+    res.render(Json(result));
+    Ok(())
+}
+
+/// 将数据库中的配方等级表转换为模拟器使用的格式
+fn recipe_level(rt: recipe_level_tables::Model) -> RecipeLevel {
+    RecipeLevel {
+        class_job_level: rt.class_job_level,
+        stars: 0,
+        suggested_craftsmanship: rt.suggested_craftsmanship,
+        suggested_control: None,
+        difficulty: rt.difficulty,
+        quality: rt.quality,
+        progress_divider: rt.progress_divider,
+        quality_divider: rt.quality_divider,
+        progress_modifier: rt.progress_modifier,
+        quality_modifier: rt.quality_modifier,
+        durability: rt.durability,
+        conditions_flag: rt.conditions_flag,
+    }
+}
+
+/// 查询配方并直接计算出模拟制作所需的配方信息
+#[handler]
+async fn recipe(req: &mut Request, depot: &mut Depot, res: &mut Response) -> Result<()> {
+    let state = depot
+        .obtain::<AppState>()
+        .map_err(|_| StatusError::internal_server_error().detail("Obtain AppState error"))?;
+    let recipe_id = req
+        .query::<u32>("recipe_id")
+        .ok_or_else(|| StatusError::bad_request().detail("Need 'recipe_id'"))?;
+    let Some((r, Some(rt))) = Recipes::find_by_id(recipe_id)
+        .find_also_related(RecipeLevelTables)
         .one(&state.conn)
         .await
         .map_err(|e| {
-            println!("recipe_info error: {e:?}");
+            println!("recipe error: {e:?}");
             StatusError::internal_server_error()
         })?
-        .ok_or_else(|| StatusError::bad_request().detail("Recipe not found"))?;
+    else {
+        return Err(StatusError::bad_request().detail("Recipe not found"));
+    };
+    let result = app_libs::recipe::new_recipe(
+        recipe_level(rt),
+        r.difficulty_factor,
+        r.quality_factor,
+        r.durability_factor,
+    );
     res.render(Json(result));
     Ok(())
 }
 
+/// 根据配方的`CollectablesMetadataKey`从对应的表中查询收藏品信息
 #[handler]
 async fn recipe_collectability(
     req: &mut Request,
@@ -310,16 +517,126 @@ async fn recipe_collectability(
     let recipe_id = req
         .query::<u32>("recipe_id")
         .ok_or_else(|| StatusError::bad_request().detail("Need 'recipe_id'"))?;
-    let result = CollectablesShopRefine::find()
-        .reverse_join(Recipes)
-        .filter(recipes::Column::Id.eq(recipe_id))
+    let recipe = Recipes::find_by_id(recipe_id)
         .one(&state.conn)
         .await
         .map_err(|_| StatusError::internal_server_error())?;
-    res.render(Json(result));
+    let row = match recipe {
+        Some(recipe) => collectables_metadata_row(&state.conn, recipe)
+            .await
+            .map_err(|_| StatusError::internal_server_error())?,
+        None => None,
+    };
+    res.render(Json(row.map(CollectablesMetadata::from)));
     Ok(())
 }
 
+/// 按配方的`CollectablesMetadataKey`从对应的表中读出收藏品信息
+async fn collectables_metadata_row(
+    db: &DatabaseConnection,
+    recipe: recipes::Model,
+) -> std::result::Result<Option<CollectablesMetadataRow>, DbErr> {
+    let Some(kind) = CollectablesMetadataKind::from_key(recipe.collectables_metadata_key) else {
+        return Ok(None);
+    };
+    // 后三种表按交纳的物品展开，需要用配方的成品区分同一行中的不同物品
+    let Some(item) = ItemWithAmount::find_by_id(recipe.item_result_id)
+        .one(db)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let (id, item_id) = (recipe.collectables_metadata, item.ingredient_id);
+    let row = match kind {
+        CollectablesMetadataKind::CollectablesShopRefine => CollectablesShopRefine::find_by_id(id)
+            .one(db)
+            .await?
+            .map(|v| CollectablesMetadataRow::Refine {
+                kind,
+                id: v.id,
+                collectability: [
+                    v.low_collectability,
+                    v.mid_collectability,
+                    v.high_collectability,
+                ]
+                .map(u32::from),
+            }),
+        CollectablesMetadataKind::CollectablesRefine => CollectablesRefine::find_by_id(id)
+            .one(db)
+            .await?
+            .map(|v| CollectablesMetadataRow::Refine {
+                kind,
+                id: v.id,
+                collectability: [
+                    v.low_collectability,
+                    v.mid_collectability,
+                    v.high_collectability,
+                ]
+                .map(u32::from),
+            }),
+        CollectablesMetadataKind::HWDCrafterSupply => {
+            let Some(v) = HwdCrafterSupply::find_by_id((id, item_id)).one(db).await? else {
+                return Ok(None);
+            };
+            let reward_ids = [
+                v.base_collectable_reward_id,
+                v.mid_collectable_reward_id,
+                v.high_collectable_reward_id,
+            ];
+            let rewards = HwdCrafterSupplyReward::find()
+                .filter(hwd_crafter_supply_reward::Column::Id.is_in(reward_ids))
+                .all(db)
+                .await?;
+            let reward = |id: u32, f: fn(&hwd_crafter_supply_reward::Model) -> u32| {
+                rewards.iter().find(|r| r.id == id).map_or(0, f)
+            };
+            Some(CollectablesMetadataRow::HWDCrafterSupply {
+                id: v.id,
+                rating: [
+                    v.base_collectable_rating,
+                    v.mid_collectable_rating,
+                    v.high_collectable_rating,
+                ]
+                .map(u32::from),
+                scrip: reward_ids.map(|id| reward(id, |r| r.script_reward_amount)),
+                exp: reward_ids.map(|id| reward(id, |r| r.exp_reward)),
+                points: reward_ids.map(|id| reward(id, |r| r.points)),
+            })
+        }
+        CollectablesMetadataKind::SatisfactionSupply => SatisfactionSupply::find()
+            .filter(satisfaction_supply::Column::Id.eq(id))
+            .filter(satisfaction_supply::Column::ItemId.eq(item_id))
+            .find_also_related(SatisfactionSupplyReward)
+            .one(db)
+            .await?
+            .map(|(v, r)| CollectablesMetadataRow::SatisfactionSupply {
+                id: v.id,
+                collectability: [
+                    v.collectability_low,
+                    v.collectability_mid,
+                    v.collectability_high,
+                ]
+                .map(u32::from),
+                scrip: r.as_ref().map_or([0; 3], |r| {
+                    [r.quantity_low, r.quantity_mid, r.quantity_high]
+                }),
+                satisfaction: r.as_ref().map_or([0; 3], |r| {
+                    [r.satisfaction_low, r.satisfaction_mid, r.satisfaction_high]
+                }),
+            }),
+        CollectablesMetadataKind::SharlayanCraftWorkSupply => {
+            SharlayanCraftWorkSupply::find_by_id((id, item_id))
+                .one(db)
+                .await?
+                .map(|v| CollectablesMetadataRow::SharlayanCraftWorkSupply {
+                    id: v.id,
+                    collectability: [v.collectability_mid, v.collectability_high].map(u32::from),
+                })
+        }
+    };
+    Ok(row)
+}
+
 // item_id: i32
 #[handler]
 async fn item_info(req: &mut Request, depot: &mut Depot, res: &mut Response) -> Result<()> {
@@ -346,20 +663,6 @@ struct ItemFoodAction {
     item_food_duration: u16,
 }
 
-#[derive(Default, Serialize)]
-struct Enhancer {
-    name: String,
-    level: u32,
-    is_hq: bool,
-
-    cm: Option<i8>,
-    cm_max: Option<i16>,
-    ct: Option<i8>,
-    ct_max: Option<i16>,
-    cp: Option<i8>,
-    cp_max: Option<i16>,
-}
-
 const MEDICINE_SEARCH_ID: u32 = 43;
 const MEALS_SEARCH_ID: u32 = 45;
 
@@ -433,22 +736,22 @@ async fn query_enhancers(conn: &DatabaseConnection, search_id: u32) -> Result<Ve
             {
                 match item_food.base_param {
                     11 => {
-                        enh.cp = Some(item_food.value);
-                        enh.cp_max = Some(item_food.max);
-                        enh_hq.cp = Some(item_food.value_hq);
-                        enh_hq.cp_max = Some(item_food.max_hq);
+                        enh.cp = Some(item_food.value.into());
+                        enh.cp_max = Some(item_food.max.into());
+                        enh_hq.cp = Some(item_food.value_hq.into());
+                        enh_hq.cp_max = Some(item_food.max_hq.into());
                     }
                     70 => {
-                        enh.cm = Some(item_food.value);
-                        enh.cm_max = Some(item_food.max);
-                        enh_hq.cm = Some(item_food.value_hq);
-                        enh_hq.cm_max = Some(item_food.max_hq);
+                        enh.cm = Some(item_food.value.into());
+                        enh.cm_max = Some(item_food.max.into());
+                        enh_hq.cm = Some(item_food.value_hq.into());
+                        enh_hq.cm_max = Some(item_food.max_hq.into());
                     }
                     71 => {
-                        enh.ct = Some(item_food.value);
-                        enh.ct_max = Some(item_food.max);
-                        enh_hq.ct = Some(item_food.value_hq);
-                        enh_hq.ct_max = Some(item_food.max_hq);
+                        enh.ct = Some(item_food.value.into());
+                        enh.ct_max = Some(item_food.max.into());
+                        enh_hq.ct = Some(item_food.value_hq.into());
+                        enh_hq.ct_max = Some(item_food.max_hq.into());
                     }
                     _ => {}
                 }
@@ -0,0 +1,22 @@
+//! 收藏品精制的收藏价值阈值，与CollectablesShopRefine表结构相同
+
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "CollectablesRefine")]
+pub struct Model {
+    #[sea_orm(column_name = "Id", primary_key)]
+    pub id: i32,
+    #[sea_orm(column_name = "LowCollectability")]
+    pub low_collectability: i32,
+    #[sea_orm(column_name = "MidCollectability")]
+    pub mid_collectability: i32,
+    #[sea_orm(column_name = "HighCollectability")]
+    pub high_collectability: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,22 @@
+//! 伊修加德重建交纳的奖励，对应游戏中的HWDCrafterSupplyReward表
+
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "HWDCrafterSupplyReward")]
+pub struct Model {
+    #[sea_orm(column_name = "Id", primary_key)]
+    pub id: i32,
+    #[sea_orm(column_name = "ScriptRewardAmount")]
+    pub script_reward_amount: i32,
+    #[sea_orm(column_name = "ExpReward")]
+    pub exp_reward: i32,
+    #[sea_orm(column_name = "Points")]
+    pub points: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -2,8 +2,11 @@
 
 pub mod prelude;
 
+pub mod collectables_refine;
 pub mod collectables_shop_refine;
 pub mod craft_types;
+pub mod hwd_crafter_supply;
+pub mod hwd_crafter_supply_reward;
 pub mod item_action;
 pub mod item_food;
 pub mod item_food_effect;
@@ -13,3 +16,6 @@ pub mod item_with_amount;
 pub mod items;
 pub mod recipe_level_tables;
 pub mod recipes;
+pub mod satisfaction_supply;
+pub mod satisfaction_supply_reward;
+pub mod sharlayan_craft_work_supply;
@@ -1,7 +1,10 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.2
 
+pub use super::collectables_refine::Entity as CollectablesRefine;
 pub use super::collectables_shop_refine::Entity as CollectablesShopRefine;
 pub use super::craft_types::Entity as CraftTypes;
+pub use super::hwd_crafter_supply::Entity as HwdCrafterSupply;
+pub use super::hwd_crafter_supply_reward::Entity as HwdCrafterSupplyReward;
 pub use super::item_action::Entity as ItemAction;
 pub use super::item_food::Entity as ItemFood;
 pub use super::item_food_effect::Entity as ItemFoodEffect;
@@ -11,3 +14,6 @@ pub use super::item_with_amount::Entity as ItemWithAmount;
 pub use super::items::Entity as Items;
 pub use super::recipe_level_tables::Entity as RecipeLevelTables;
 pub use super::recipes::Entity as Recipes;
+pub use super::satisfaction_supply::Entity as SatisfactionSupply;
+pub use super::satisfaction_supply_reward::Entity as SatisfactionSupplyReward;
+pub use super::sharlayan_craft_work_supply::Entity as SharlayanCraftWorkSupply;
@@ -71,6 +71,42 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     CollectablesShopRefine,
+    #[sea_orm(
+        belongs_to = "super::hwd_crafter_supply::Entity",
+        from = "Column::CollectablesMetadata",
+        to = "super::hwd_crafter_supply::Column::Id",
+        on_condition = r#"Column::CollectablesMetadataKey.eq(2)"#,
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    HwdCrafterSupply,
+    #[sea_orm(
+        belongs_to = "super::satisfaction_supply::Entity",
+        from = "Column::CollectablesMetadata",
+        to = "super::satisfaction_supply::Column::Id",
+        on_condition = r#"Column::CollectablesMetadataKey.eq(3)"#,
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    SatisfactionSupply,
+    #[sea_orm(
+        belongs_to = "super::sharlayan_craft_work_supply::Entity",
+        from = "Column::CollectablesMetadata",
+        to = "super::sharlayan_craft_work_supply::Column::Id",
+        on_condition = r#"Column::CollectablesMetadataKey.eq(4)"#,
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    SharlayanCraftWorkSupply,
+    #[sea_orm(
+        belongs_to = "super::collectables_refine::Entity",
+        from = "Column::CollectablesMetadata",
+        to = "super::collectables_refine::Column::Id",
+        on_condition = r#"Column::CollectablesMetadataKey.eq(5)"#,
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    CollectablesRefine,
 }
 
 impl Related<super::craft_types::Entity> for Entity {
@@ -97,4 +133,28 @@ impl Related<super::collectables_shop_refine::Entity> for Entity {
     }
 }
 
+impl Related<super::hwd_crafter_supply::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::HwdCrafterSupply.def()
+    }
+}
+
+impl Related<super::satisfaction_supply::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SatisfactionSupply.def()
+    }
+}
+
+impl Related<super::sharlayan_craft_work_supply::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SharlayanCraftWorkSupply.def()
+    }
+}
+
+impl Related<super::collectables_refine::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CollectablesRefine.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,43 @@
+//! 老主顾的收藏品交纳，对应游戏中SatisfactionSupply表的子行
+
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "SatisfactionSupply")]
+pub struct Model {
+    #[sea_orm(column_name = "Id", primary_key, auto_increment = false)]
+    pub id: i32,
+    #[sea_orm(column_name = "SubRowId", primary_key, auto_increment = false)]
+    pub sub_row_id: i32,
+    #[sea_orm(column_name = "ItemId")]
+    pub item_id: i32,
+    #[sea_orm(column_name = "CollectabilityLow")]
+    pub collectability_low: i32,
+    #[sea_orm(column_name = "CollectabilityMid")]
+    pub collectability_mid: i32,
+    #[sea_orm(column_name = "CollectabilityHigh")]
+    pub collectability_high: i32,
+    #[sea_orm(column_name = "RewardId")]
+    pub reward_id: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::satisfaction_supply_reward::Entity",
+        from = "Column::RewardId",
+        to = "super::satisfaction_supply_reward::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    SatisfactionSupplyReward,
+}
+
+impl Related<super::satisfaction_supply_reward::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SatisfactionSupplyReward.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
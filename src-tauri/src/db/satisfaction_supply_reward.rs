@@ -0,0 +1,28 @@
+//! 老主顾交纳的奖励，对应游戏中的SatisfactionSupplyReward表，工票数量只保留第一种货币
+
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "SatisfactionSupplyReward")]
+pub struct Model {
+    #[sea_orm(column_name = "Id", primary_key)]
+    pub id: i32,
+    #[sea_orm(column_name = "QuantityLow")]
+    pub quantity_low: i32,
+    #[sea_orm(column_name = "QuantityMid")]
+    pub quantity_mid: i32,
+    #[sea_orm(column_name = "QuantityHigh")]
+    pub quantity_high: i32,
+    #[sea_orm(column_name = "SatisfactionLow")]
+    pub satisfaction_low: i32,
+    #[sea_orm(column_name = "SatisfactionMid")]
+    pub satisfaction_mid: i32,
+    #[sea_orm(column_name = "SatisfactionHigh")]
+    pub satisfaction_high: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -20,35 +20,52 @@
 )]
 
 use std::{
-    collections::{hash_map::Entry, BTreeMap, HashMap},
-    sync::Arc,
+    collections::{BTreeMap, HashMap},
+    time::Duration,
 };
 
 use app_libs::{
-    analyzer::{rand_simulations, scope_of_application::Scope},
-    ffxiv_crafting::{Actions, Attributes, Recipe, Status},
+    analyzer::{
+        enhancer_optimizer, exact_distribution, feasibility_map,
+        rand_simulations::{
+            self, CollectablesMetadata, CollectablesMetadataKind, CollectablesMetadataRow,
+        },
+        scope_of_application::Scope,
+        stat_weights,
+    },
+    bom::{self, Bom, BomItem, BomRecipe},
+    enhancer::{self, EnhancedAttributes, Enhancer},
+    ffxiv_crafting::{Actions, Attributes, Recipe, RecipeLevel, Status},
+    macros::{GenerateOptions, ParseResult},
+    recipe::{HqIngredientsPlan, Ingredient, RecipeFilter},
+    rotation::Rotation,
     solver::{
-        depth_first_search_solver, normal_progress_solver, raphael, reflect_solver, rika_solver,
-        Solver, SolverHash,
+        expert_solver, portfolio,
+        registry::{self, SolverConfig, SolverInfo},
+        CancelToken, ProgressSink,
     },
-    SimulateOneStepResult, SimulateResult,
+    trace::{ScriptedStep, SimulateTraceResult},
+    SimulateOneStepResult, SimulateResult, SimulateRotationOneStepResult, SimulateRotationResult,
 };
 use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
-use rand::rng;
-use sea_orm::{entity::*, query::*, Database, DatabaseConnection, FromQueryResult};
+use sea_orm::{
+    entity::*, query::*, sea_query::Query, Database, DatabaseConnection, DbErr, FromQueryResult,
+    SelectModel, Selector,
+};
 use serde::Serialize;
-use tauri::{path::BaseDirectory, Manager};
-use tokio::sync::{Mutex, OnceCell};
+use tauri::{path::BaseDirectory, Emitter, Manager};
+use tokio::sync::OnceCell;
 
 mod db;
-mod memoization_solver;
-mod muscle_memory_solver;
-mod rika_tnze_solver;
+mod recipe_filter;
+mod solve_jobs;
 
 use db::{
-    collectables_shop_refine, craft_types, item_action, item_food, item_food_effect,
-    item_with_amount, items, prelude::*, recipe_level_tables, recipes,
+    craft_types, hwd_crafter_supply_reward, item_action, item_food, item_food_effect,
+    item_with_amount, items, prelude::*, recipe_level_tables, recipes, satisfaction_supply,
 };
+use recipe_filter::ApplyRecipeFilter;
+use solve_jobs::{JobId, JobStatus, PrepareConfig, SolveJobs};
 
 /// 创建新的Recipe对象，蕴含了模拟一次制作过程所必要的全部配方信息
 #[tauri::command(async)]
@@ -68,6 +85,48 @@ async fn recipe_level_table(
     Ok(rt)
 }
 
+/// 将数据库中的配方等级表转换为模拟器使用的格式
+fn recipe_level(rt: recipe_level_tables::Model) -> RecipeLevel {
+    RecipeLevel {
+        class_job_level: rt.class_job_level as _,
+        stars: 0,
+        suggested_craftsmanship: rt.suggested_craftsmanship as _,
+        suggested_control: None,
+        difficulty: rt.difficulty as _,
+        quality: rt.quality as _,
+        progress_divider: rt.progress_divider as _,
+        quality_divider: rt.quality_divider as _,
+        progress_modifier: rt.progress_modifier as _,
+        quality_modifier: rt.quality_modifier as _,
+        durability: rt.durability as _,
+        conditions_flag: rt.conditions_flag as _,
+    }
+}
+
+/// 查询配方并直接计算出模拟制作所需的配方信息
+#[tauri::command(async)]
+async fn recipe(
+    recipe_id: i32,
+    app_state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Recipe, String> {
+    let db = app_state.get_db(app_handle).await.map_err(err_to_string)?;
+    let Some((r, Some(rt))) = Recipes::find_by_id(recipe_id)
+        .find_also_related(RecipeLevelTables)
+        .one(db)
+        .await
+        .map_err(err_to_string)?
+    else {
+        return Err(String::from("unknown-recipe"));
+    };
+    Ok(app_libs::recipe::new_recipe(
+        recipe_level(rt),
+        r.difficulty_factor as u16,
+        r.quality_factor as u16,
+        r.durability_factor as u16,
+    ))
+}
+
 #[tauri::command(async)]
 fn new_status(attrs: Attributes, recipe: Recipe) -> Result<Status, String> {
     app_libs::new_status(attrs, recipe)
@@ -78,13 +137,62 @@ fn simulate(status: Status, actions: Vec<Actions>) -> SimulateResult {
     app_libs::simulate(status, actions)
 }
 
+#[tauri::command(async)]
+fn simulate_trace(status: Status, actions: Vec<Actions>) -> SimulateTraceResult {
+    app_libs::trace::simulate_trace(status, actions)
+}
+
+#[tauri::command(async)]
+fn simulate_scripted(
+    status: Status,
+    script: Vec<ScriptedStep>,
+) -> Result<SimulateTraceResult, String> {
+    app_libs::trace::simulate_scripted(status, script)
+}
+
+#[tauri::command(async)]
+fn simulate_rotation(status: Status, rotation: String) -> Result<SimulateRotationResult, String> {
+    let rotation: Rotation = rotation.parse().map_err(err_to_string)?;
+    Ok(app_libs::simulate_rotation(status, &rotation))
+}
+
+#[tauri::command(async)]
+fn simulate_rotation_one_step(
+    status: Status,
+    rotation: String,
+    pc: usize,
+    force_success: bool,
+    seed: Option<u64>,
+) -> Result<SimulateRotationOneStepResult, String> {
+    let rotation: Rotation = rotation.parse().map_err(err_to_string)?;
+    let mut rng = app_libs::new_step_rng(seed, status.step);
+    Ok(app_libs::simulate_rotation_step(
+        status,
+        &rotation,
+        pc,
+        force_success,
+        &mut rng,
+    ))
+}
+
+#[tauri::command(async)]
+fn parse_macro(text: String) -> ParseResult {
+    app_libs::macros::parse(&text)
+}
+
+#[tauri::command(async)]
+fn generate_macro(actions: Vec<Actions>, options: GenerateOptions) -> Vec<Vec<String>> {
+    app_libs::macros::generate(&actions, &options)
+}
+
 #[tauri::command(async)]
 fn simulate_one_step(
     mut status: Status,
     action: Actions,
     force_success: bool,
+    seed: Option<u64>,
 ) -> Result<SimulateOneStepResult, String> {
-    let mut rng = rng();
+    let mut rng = app_libs::new_step_rng(seed, status.step);
     app_libs::simulate_one_step(&mut status, action, force_success, &mut rng)
         .map(|is_success| SimulateOneStepResult { status, is_success })
         .map_err(err_to_string)
@@ -105,6 +213,32 @@ fn high_quality_probability(status: Status) -> Option<i32> {
     app_libs::high_quality_probability(status)
 }
 
+/// 根据各素材中HQ素材的数量计算初期品质
+#[tauri::command(async)]
+fn initial_quality(
+    recipe: Recipe,
+    material_quality_factor: u32,
+    ingredients: Vec<Ingredient>,
+) -> u32 {
+    app_libs::recipe::initial_quality(&recipe, material_quality_factor, &ingredients)
+}
+
+/// 计算执行技能序列推满品质至少需要的HQ素材
+#[tauri::command(async)]
+fn required_hq_ingredients(
+    status: Status,
+    actions: Vec<Actions>,
+    material_quality_factor: u32,
+    ingredients: Vec<Ingredient>,
+) -> Option<HqIngredientsPlan> {
+    app_libs::recipe::required_hq_ingredients(
+        &status,
+        &actions,
+        material_quality_factor,
+        &ingredients,
+    )
+}
+
 fn err_to_string<T: ToString>(v: T) -> String {
     v.to_string()
 }
@@ -129,6 +263,32 @@ struct RecipeInfo {
     can_hq: bool,
 }
 
+/// 选出构造RecipeInfo所需的列，`query`需要已经连接了物品和职业表
+fn select_recipe_info(query: Select<Recipes>) -> Selector<SelectModel<RecipeInfo>> {
+    query
+        .select_only()
+        .column_as(recipes::Column::Id, "id")
+        .column_as(recipes::Column::RecipeLevelId, "rlv")
+        .column_as(items::Column::Id, "item_id")
+        .column_as(items::Column::Name, "item_name")
+        .column_as(item_with_amount::Column::Amount, "item_amount")
+        .column_as(craft_types::Column::Name, "job")
+        .column_as(recipes::Column::DifficultyFactor, "difficulty_factor")
+        .column_as(recipes::Column::QualityFactor, "quality_factor")
+        .column_as(recipes::Column::DurabilityFactor, "durability_factor")
+        .column_as(
+            recipes::Column::MaterialQualityFactor,
+            "material_quality_factor",
+        )
+        .column_as(
+            recipes::Column::RequiredCraftsmanship,
+            "required_craftsmanship",
+        )
+        .column_as(recipes::Column::RequiredControl, "required_control")
+        .column_as(recipes::Column::CanHq, "can_hq")
+        .into_model::<RecipeInfo>()
+}
+
 #[tauri::command(async)]
 async fn recipe_table(
     page_id: u64,
@@ -137,6 +297,7 @@ async fn recipe_table(
     recipe_level: Option<i32>,
     job_level_min: Option<i32>,
     job_level_max: Option<i32>,
+    filter: Option<RecipeFilter<i32>>,
     app_state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
 ) -> Result<(Vec<RecipeInfo>, u64), String> {
@@ -162,33 +323,34 @@ async fn recipe_table(
     if let Some(job_level_max) = job_level_max {
         query = query.filter(recipe_level_tables::Column::ClassJobLevel.lte(job_level_max))
     }
-    let paginate = query
-        .column_as(recipes::Column::Id, "id")
-        .column_as(recipes::Column::RecipeLevelId, "rlv")
-        .column_as(items::Column::Id, "item_id")
-        .column_as(items::Column::Name, "item_name")
-        .column_as(item_with_amount::Column::Amount, "item_amount")
-        .column_as(craft_types::Column::Name, "job")
-        .column_as(recipes::Column::DifficultyFactor, "difficulty_factor")
-        .column_as(recipes::Column::QualityFactor, "quality_factor")
-        .column_as(recipes::Column::DurabilityFactor, "durability_factor")
-        .column_as(
-            recipes::Column::MaterialQualityFactor,
-            "material_quality_factor",
-        )
-        .column_as(
-            recipes::Column::RequiredCraftsmanship,
-            "required_craftsmanship",
-        )
-        .column_as(recipes::Column::RequiredControl, "required_control")
-        .column_as(recipes::Column::CanHq, "can_hq")
-        .into_model::<RecipeInfo>()
-        .paginate(db, 200);
+    let paginate = select_recipe_info(filter.unwrap_or_default().apply(query)).paginate(db, 200);
     let p = paginate.num_pages().await.map_err(err_to_string)?;
     let data = paginate.fetch_page(page_id).await.map_err(err_to_string)?;
     Ok((data, p))
 }
 
+#[tauri::command(async)]
+async fn recipe_info(
+    recipe_id: i32,
+    app_state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<RecipeInfo, String> {
+    let db = app_state.get_db(app_handle).await.map_err(err_to_string)?;
+    let query = Recipes::find_by_id(recipe_id)
+        .join(JoinType::InnerJoin, recipes::Relation::CraftTypes.def())
+        .join(JoinType::InnerJoin, recipes::Relation::ItemWithAmount.def())
+        .join(
+            JoinType::InnerJoin,
+            recipes::Relation::RecipeLevelTables.def(),
+        )
+        .join(JoinType::InnerJoin, item_with_amount::Relation::Items.def());
+    select_recipe_info(query)
+        .one(db)
+        .await
+        .map_err(err_to_string)?
+        .ok_or_else(|| String::from("unknown-recipe"))
+}
+
 #[tauri::command(async)]
 async fn recipes_ingredientions(
     recipe_id: i32,
@@ -211,30 +373,251 @@ async fn recipes_ingredientions(
     Ok(needs.into_iter().collect())
 }
 
-// #[derive(Serialize)]
-// enum CollectablesMetadata {
-//     CollectablesShopRefine(CollectablesShopRefine),
-//     // HWDCrafterSupply,
-//     // SatisfactionSupply,
-//     // SharlayanCraftWorkSupply,
-//     // CollectablesRefine,
-//     Unknown(u16),
-// }
+#[derive(Serialize)]
+struct IngredientUsage {
+    #[serde(flatten)]
+    recipe: RecipeInfo,
+    /// 每次制作消耗该物品的数量
+    amount: i32,
+}
+
+/// 分页查询使用了指定物品作为素材的所有配方
+#[tauri::command(async)]
+async fn recipes_using_item(
+    item_id: i32,
+    page_id: u64,
+    app_state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(Vec<IngredientUsage>, u64), String> {
+    let db = app_state.get_db(app_handle).await?;
+    let used_in = Query::select()
+        .column(item_with_amount::Column::RecipeId)
+        .from(ItemWithAmount)
+        .and_where(item_with_amount::Column::IngredientId.eq(item_id))
+        .to_owned();
+    let query = Recipes::find()
+        .join(JoinType::InnerJoin, recipes::Relation::CraftTypes.def())
+        .join(JoinType::InnerJoin, recipes::Relation::ItemWithAmount.def())
+        .join(
+            JoinType::InnerJoin,
+            recipes::Relation::RecipeLevelTables.def(),
+        )
+        .join(JoinType::InnerJoin, item_with_amount::Relation::Items.def())
+        .filter(recipes::Column::Id.in_subquery(used_in))
+        .order_by(recipes::Column::Id, Order::Asc);
+    let paginate = select_recipe_info(query).paginate(db, 200);
+    let p = paginate.num_pages().await.map_err(err_to_string)?;
+    let recipes = paginate.fetch_page(page_id).await.map_err(err_to_string)?;
+
+    let mut amounts = HashMap::new();
+    let ingredients = ItemWithAmount::find()
+        .filter(item_with_amount::Column::IngredientId.eq(item_id))
+        .filter(item_with_amount::Column::RecipeId.is_in(recipes.iter().map(|r| r.id)))
+        .all(db)
+        .await
+        .map_err(err_to_string)?;
+    for v in ingredients {
+        *amounts.entry(v.recipe_id).or_insert(0) += v.amount;
+    }
+    let data = recipes
+        .into_iter()
+        .map(|recipe| IngredientUsage {
+            amount: amounts.get(&Some(recipe.id)).copied().unwrap_or(0),
+            recipe,
+        })
+        .collect();
+    Ok((data, p))
+}
+
+#[derive(FromQueryResult)]
+struct BomRecipeRow {
+    id: i32,
+    job: String,
+    item_amount: i32,
+}
+
+/// 查询物品的名称以及制作它的配方，有多个配方时使用ID最小的一个
+async fn bom_item(db: &DatabaseConnection, item_id: u32) -> Result<BomItem, DbErr> {
+    let name = Items::find_by_id(item_id as i32)
+        .one(db)
+        .await?
+        .map(|v| v.name)
+        .unwrap_or_default();
+    let Some(r) = Recipes::find()
+        .join(JoinType::InnerJoin, recipes::Relation::CraftTypes.def())
+        .join(JoinType::InnerJoin, recipes::Relation::ItemWithAmount.def())
+        .filter(item_with_amount::Column::IngredientId.eq(item_id))
+        .order_by_asc(recipes::Column::Id)
+        .select_only()
+        .column_as(recipes::Column::Id, "id")
+        .column_as(craft_types::Column::Name, "job")
+        .column_as(item_with_amount::Column::Amount, "item_amount")
+        .into_model::<BomRecipeRow>()
+        .one(db)
+        .await?
+    else {
+        return Ok(BomItem { name, recipe: None });
+    };
+    let ingredients = ItemWithAmount::find()
+        .filter(item_with_amount::Column::RecipeId.eq(r.id))
+        .all(db)
+        .await?;
+    Ok(BomItem {
+        name,
+        recipe: Some(BomRecipe {
+            recipe_id: r.id as u32,
+            job: r.job,
+            item_amount: r.item_amount as u32,
+            ingredients: ingredients
+                .into_iter()
+                .map(|v| (v.ingredient_id as u32, v.amount as u32))
+                .collect(),
+        }),
+    })
+}
+
+/// 递归展开制作目标物品所需的全部半成品和原材料，`targets`为`(item_id, amount)`
+#[tauri::command(async)]
+async fn bom(
+    targets: Vec<(u32, u32)>,
+    app_state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Bom, String> {
+    let db = app_state.get_db(app_handle).await?;
+    let items = bom::collect_items(&targets, |id| bom_item(db, id))
+        .await
+        .map_err(err_to_string)?;
+    bom::resolve(&targets, &items)
+}
 
+/// 根据配方的`CollectablesMetadataKey`从对应的表中查询收藏品信息
 #[tauri::command(async)]
 async fn recipe_collectability(
     recipe_id: i32,
     app_state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<Option<collectables_shop_refine::Model>, String> {
+) -> Result<Option<CollectablesMetadata>, String> {
     let db = app_state.get_db(app_handle).await?;
-    let result = CollectablesShopRefine::find()
-        .reverse_join(Recipes)
-        .filter(recipes::Column::Id.eq(recipe_id))
+    let Some(recipe) = Recipes::find_by_id(recipe_id)
         .one(db)
         .await
-        .map_err(err_to_string)?;
-    Ok(result)
+        .map_err(err_to_string)?
+    else {
+        return Ok(None);
+    };
+    let Some(kind) = CollectablesMetadataKind::from_key(recipe.collectables_metadata_key as u16)
+    else {
+        return Ok(None);
+    };
+    // 后三种表按交纳的物品展开，需要用配方的成品区分同一行中的不同物品
+    let Some(item) = ItemWithAmount::find_by_id(recipe.item_result_id)
+        .one(db)
+        .await
+        .map_err(err_to_string)?
+    else {
+        return Ok(None);
+    };
+    let (id, item_id) = (recipe.collectables_metadata, item.ingredient_id);
+    let row = match kind {
+        CollectablesMetadataKind::CollectablesShopRefine => CollectablesShopRefine::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(err_to_string)?
+            .map(|v| CollectablesMetadataRow::Refine {
+                kind,
+                id: v.id as u32,
+                collectability: [
+                    v.low_collectability,
+                    v.mid_collectability,
+                    v.high_collectability,
+                ]
+                .map(|c| c as u32),
+            }),
+        CollectablesMetadataKind::CollectablesRefine => CollectablesRefine::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(err_to_string)?
+            .map(|v| CollectablesMetadataRow::Refine {
+                kind,
+                id: v.id as u32,
+                collectability: [
+                    v.low_collectability,
+                    v.mid_collectability,
+                    v.high_collectability,
+                ]
+                .map(|c| c as u32),
+            }),
+        CollectablesMetadataKind::HWDCrafterSupply => {
+            let Some(v) = HwdCrafterSupply::find_by_id((id, item_id))
+                .one(db)
+                .await
+                .map_err(err_to_string)?
+            else {
+                return Ok(None);
+            };
+            let reward_ids = [
+                v.base_collectable_reward_id,
+                v.mid_collectable_reward_id,
+                v.high_collectable_reward_id,
+            ];
+            let rewards = HwdCrafterSupplyReward::find()
+                .filter(hwd_crafter_supply_reward::Column::Id.is_in(reward_ids))
+                .all(db)
+                .await
+                .map_err(err_to_string)?;
+            let reward = |id: i32, f: fn(&hwd_crafter_supply_reward::Model) -> i32| {
+                rewards
+                    .iter()
+                    .find(|r| r.id == id)
+                    .map_or(0, |r| f(r) as u32)
+            };
+            Some(CollectablesMetadataRow::HWDCrafterSupply {
+                id: v.id as u32,
+                rating: [
+                    v.base_collectable_rating,
+                    v.mid_collectable_rating,
+                    v.high_collectable_rating,
+                ]
+                .map(|c| c as u32),
+                scrip: reward_ids.map(|id| reward(id, |r| r.script_reward_amount)),
+                exp: reward_ids.map(|id| reward(id, |r| r.exp_reward)),
+                points: reward_ids.map(|id| reward(id, |r| r.points)),
+            })
+        }
+        CollectablesMetadataKind::SatisfactionSupply => SatisfactionSupply::find()
+            .filter(satisfaction_supply::Column::Id.eq(id))
+            .filter(satisfaction_supply::Column::ItemId.eq(item_id))
+            .find_also_related(SatisfactionSupplyReward)
+            .one(db)
+            .await
+            .map_err(err_to_string)?
+            .map(|(v, r)| CollectablesMetadataRow::SatisfactionSupply {
+                id: v.id as u32,
+                collectability: [
+                    v.collectability_low,
+                    v.collectability_mid,
+                    v.collectability_high,
+                ]
+                .map(|c| c as u32),
+                scrip: r.as_ref().map_or([0; 3], |r| {
+                    [r.quantity_low, r.quantity_mid, r.quantity_high].map(|c| c as u32)
+                }),
+                satisfaction: r.as_ref().map_or([0; 3], |r| {
+                    [r.satisfaction_low, r.satisfaction_mid, r.satisfaction_high].map(|c| c as u32)
+                }),
+            }),
+        CollectablesMetadataKind::SharlayanCraftWorkSupply => {
+            SharlayanCraftWorkSupply::find_by_id((id, item_id))
+                .one(db)
+                .await
+                .map_err(err_to_string)?
+                .map(|v| CollectablesMetadataRow::SharlayanCraftWorkSupply {
+                    id: v.id as u32,
+                    collectability: [v.collectability_mid, v.collectability_high].map(|c| c as u32),
+                })
+        }
+    };
+    Ok(row.map(CollectablesMetadata::from))
 }
 
 #[tauri::command(async)]
@@ -259,20 +642,6 @@ struct ItemFoodAction {
     item_food_duration: u16,
 }
 
-#[derive(Default, Serialize)]
-struct Enhancer {
-    name: String,
-    level: u32,
-    is_hq: bool,
-
-    cm: Option<i32>,
-    cm_max: Option<i32>,
-    ct: Option<i32>,
-    ct_max: Option<i32>,
-    cp: Option<i32>,
-    cp_max: Option<i32>,
-}
-
 const MEDICINE_SEARCH_ID: u32 = 43;
 const MEALS_SEARCH_ID: u32 = 45;
 
@@ -312,6 +681,12 @@ async fn meals_table(
     query_enhancers(db, MEALS_SEARCH_ID).await
 }
 
+/// 将食物和药水应用到基础属性上，返回增益后的属性及各项加成
+#[tauri::command(async)]
+fn apply_enhancers(attributes: Attributes, enhancers: Vec<Enhancer>) -> EnhancedAttributes {
+    enhancer::apply_enhancers(attributes, &enhancers)
+}
+
 async fn query_enhancers(
     conn: &DatabaseConnection,
     search_id: u32,
@@ -391,20 +766,32 @@ async fn query_enhancers(
     Ok(result)
 }
 
-type SolverInstance = Arc<Mutex<Option<Box<dyn Solver + Send>>>>;
 struct AppState {
-    solver_list: Mutex<HashMap<SolverHash, SolverInstance>>,
+    // 当前所有求解共用的取消标记，取消后会被替换为新的标记
+    cancel_token: std::sync::Mutex<CancelToken>,
+    solve_jobs: SolveJobs,
     db: OnceCell<DatabaseConnection>,
 }
 
 impl AppState {
     fn new() -> Self {
         Self {
-            solver_list: Mutex::new(HashMap::new()),
+            cancel_token: std::sync::Mutex::new(CancelToken::new()),
+            solve_jobs: SolveJobs::new(2),
             db: OnceCell::new(),
         }
     }
 
+    /// 在当前的取消标记下运行求解器，求解过程中被取消时返回错误
+    fn run_solver<T>(&self, f: impl FnOnce(&CancelToken) -> T) -> Result<T, String> {
+        let cancel = self.cancel_token.lock().unwrap().clone();
+        let result = f(&cancel);
+        if cancel.is_cancelled() {
+            return Err(String::from("solve-cancelled"));
+        }
+        Ok(result)
+    }
+
     async fn get_db(&self, app_handle: tauri::AppHandle) -> Result<&DatabaseConnection, String> {
         const ESCAPE_SET: &AsciiSet = &CONTROLS.add(b'?').add(b'#');
         let path = app_handle
@@ -419,158 +806,174 @@ impl AppState {
     }
 }
 
-#[tauri::command(async)]
-async fn create_solver(
+/// 在后台建立动态规划求解器的表格并立即返回任务ID，任务结束后可以用`read_solver`读取结果
+#[tauri::command]
+fn create_solver(
     status: Status,
-    use_muscle_memory: bool,
-    use_manipulation: bool,
-    use_observe: bool,
+    config: PrepareConfig,
     app_state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
-    let key = SolverHash {
-        attributes: status.attributes,
-        recipe: status.recipe,
-    };
-    let solver_slot = {
-        let mut list = app_state.solver_list.lock().await;
-        match list.entry(key.clone()) {
-            Entry::Vacant(o) => o.insert(Arc::new(Mutex::new(None))).clone(),
-            Entry::Occupied(ref o) => {
-                return match o.get().try_lock() {
-                    Ok(_) => Err("solver-already-exist".into()),
-                    Err(_) => Err("solver-is-creating".into()),
-                }
-            }
-        }
-    };
-    // let solver: Box<dyn Solver + Send> = Box::new(memory_search_solver::Solver::new(status));
-    let solver: Box<dyn Solver + Send> = if use_muscle_memory {
-        Box::new(muscle_memory_solver::PreprogressSolver::new(
-            status,
-            use_manipulation,
-            8,
-            use_observe,
-        ))
-    } else {
-        Box::new(reflect_solver::QualitySolver::new(
-            status,
-            use_manipulation,
-            8 + 1,
-            use_observe,
-        ))
-    };
-    *solver_slot.lock().await = Some(solver);
-    Ok(())
+) -> Result<JobId, String> {
+    app_state.solve_jobs.prepare(status, config)
 }
 
-/// 调用求解器
+/// 调用建好的求解器，`status`的属性和配方与建表时不同时返回错误"solver-doesn-t-exist"
 #[tauri::command(async)]
-async fn read_solver(
+fn read_solver(
+    id: JobId,
     status: Status,
     app_state: tauri::State<'_, AppState>,
 ) -> Result<Vec<Actions>, String> {
-    let key = SolverHash {
-        attributes: status.attributes,
-        recipe: status.recipe,
-    };
-    let result = app_state
-        .solver_list
-        .lock()
-        .await
-        .get(&key)
-        .ok_or_else(|| "solver-doesn-t-exist".to_string())?
-        .lock()
-        .await
-        .as_ref()
-        .ok_or_else(|| "solver-isn-t-prepared".to_string())?
-        .read_all(&status);
-    Ok(result)
+    app_state.solve_jobs.read(id, &status)
 }
 
-#[tauri::command(async)]
-fn rika_solve(status: Status) -> Vec<Actions> {
-    rika_solver::solve(status)
+#[derive(Serialize, Clone)]
+struct SolverEvent<T> {
+    solver: &'static str,
+    value: T,
+}
+
+/// 把求解器的进度和中间结果作为事件发送给调用求解器的窗口
+struct WindowProgress {
+    window: tauri::Window,
+    solver: &'static str,
+}
+
+impl WindowProgress {
+    fn emit<T: Serialize + Clone>(&self, event: &str, value: T) {
+        let solver = self.solver;
+        let _ = self.window.emit(event, SolverEvent { solver, value });
+    }
+}
+
+impl ProgressSink for WindowProgress {
+    fn progress(&self, done: usize) {
+        self.emit("solver-progress", done);
+    }
+
+    fn intermediate(&self, actions: &[Actions]) {
+        self.emit("solver-intermediate", actions);
+    }
+}
+
+/// 列出所有求解器及其支持的功能
+#[tauri::command]
+fn solver_list() -> &'static [SolverInfo] {
+    &registry::SOLVERS
 }
 
+/// 用指定的求解器从`status`开始求解，进度和中间结果以事件的形式发送给窗口
 #[tauri::command(async)]
-fn rika_solve_tnzever(
+fn solve(
     status: Status,
-    use_manipulation: bool,
-    use_wast_not: usize,
-    use_observe: bool,
-    reduce_steps: bool,
-) -> Vec<Actions> {
-    rika_tnze_solver::solve(
-        status,
-        use_manipulation,
-        use_wast_not,
-        use_observe,
-        reduce_steps,
-    )
+    config: SolverConfig,
+    window: tauri::Window,
+    app_state: tauri::State<'_, AppState>,
+) -> Result<Vec<Actions>, String> {
+    let sink = WindowProgress {
+        window,
+        solver: config.info().name,
+    };
+    app_state.run_solver(|cancel| registry::solve(&config, status, cancel, &sink))?
 }
 
+/// 在`time_limit`毫秒内用多个求解器同时求解，返回经过模拟检验并按`objective`排序的全部结果
 #[tauri::command(async)]
-fn dfs_solve(status: Status, depth: usize, specialist: bool) -> Vec<Actions> {
-    depth_first_search_solver::solve(status, depth, specialist)
+fn solve_portfolio(
+    status: Status,
+    configs: Vec<SolverConfig>,
+    time_limit: u64,
+    objective: Option<portfolio::Objective>,
+    app_state: tauri::State<'_, AppState>,
+) -> Result<Vec<portfolio::PortfolioEntry>, String> {
+    app_state.run_solver(|cancel| {
+        portfolio::solve(
+            status,
+            configs,
+            Duration::from_millis(time_limit),
+            objective.unwrap_or_default(),
+            cancel,
+        )
+    })
 }
 
-#[tauri::command(async)]
-fn nq_solve(status: Status, depth: usize, specialist: bool) -> Vec<Actions> {
-    normal_progress_solver::solve(status, depth, specialist)
+/// 中止所有正在运行的求解器和求解任务，被中止的求解命令会返回错误"solve-cancelled"
+#[tauri::command]
+fn cancel_solve(app_state: tauri::State<'_, AppState>) {
+    let mut cancel_token = app_state.cancel_token.lock().unwrap();
+    cancel_token.cancel();
+    *cancel_token = CancelToken::new();
+    app_state.solve_jobs.cancel_all();
 }
 
-#[tauri::command(async)]
-fn reflect_solve(
+/// 在后台提交一个求解任务并立即返回任务ID
+#[tauri::command]
+fn start_solve(
     status: Status,
-    use_manipulation: bool,
-    use_waste_not: usize,
-    use_observe: bool,
-) -> Vec<Actions> {
-    reflect_solver::solve(status.clone(), use_manipulation, use_waste_not, use_observe)
+    config: SolverConfig,
+    app_state: tauri::State<'_, AppState>,
+) -> Result<JobId, String> {
+    app_state.solve_jobs.start(status, config)
 }
 
+#[tauri::command]
+fn poll_solve(id: JobId, app_state: tauri::State<'_, AppState>) -> Result<JobStatus, String> {
+    app_state.solve_jobs.poll(id)
+}
+
+#[tauri::command]
+fn cancel_solve_job(id: JobId, app_state: tauri::State<'_, AppState>) -> Result<(), String> {
+    app_state.solve_jobs.cancel(id)
+}
+
+/// 取出已结束的求解任务的结果，任务被取消时返回错误"solve-cancelled"
+#[tauri::command]
+fn solve_result(id: JobId, app_state: tauri::State<'_, AppState>) -> Result<Vec<Actions>, String> {
+    app_state.solve_jobs.result(id)
+}
+
+/// 设置同时运行的求解任务数量的上限，超出的任务会排队等待
+#[tauri::command]
+fn set_max_concurrent_solves(max: usize, app_state: tauri::State<'_, AppState>) {
+    app_state.solve_jobs.set_max_running(max);
+}
+
+/// 根据包含球色在内的当前状态给出下一步的技能，用于高难度配方的逐步求解。
+/// 该求解器每步只给出一个技能，因此不在[`registry`]中。被中止时返回错误"solve-cancelled"
 #[tauri::command(async)]
-fn raphael_solve(
+fn expert_solve(
     status: Status,
-    target_quality: Option<u32>,
-    use_manipulation: bool,
-    use_heart_and_soul: bool,
-    use_quick_innovation: bool,
-    use_trained_eye: bool,
-    backload_progress: bool,
-    adversarial: bool,
-    unsound_branch_pruning: bool,
-) -> Vec<Actions> {
-    raphael::solve(
-        status.clone(),
-        target_quality,
-        use_manipulation,
-        use_heart_and_soul,
-        use_quick_innovation,
-        use_trained_eye,
-        backload_progress,
-        adversarial,
-        unsound_branch_pruning,
-    )
+    depth: usize,
+    specialist: bool,
+    objective: expert_solver::Objective,
+    app_state: tauri::State<'_, AppState>,
+) -> Result<Option<expert_solver::Suggestion>, String> {
+    app_state.run_solver(|cancel| {
+        expert_solver::ExpertSolver::new(depth, specialist, objective, cancel.clone())
+            .suggest(&status)
+    })
 }
 
-/// 释放求解器
+/// 寻找能达成制作目标的最便宜的食物和药水组合，被更便宜的组合支配的候选不会调用求解器
 #[tauri::command(async)]
-async fn destroy_solver(
-    status: Status,
+fn optimize_enhancers(
+    attributes: Attributes,
+    recipe: Recipe,
+    meals: Vec<Enhancer>,
+    medicines: Vec<Enhancer>,
+    options: enhancer_optimizer::OptimizeOptions,
     app_state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
-    let key = SolverHash {
-        attributes: status.attributes,
-        recipe: status.recipe,
-    };
-    app_state
-        .solver_list
-        .lock()
-        .await
-        .remove(&key)
-        .ok_or_else(|| "solver not exists".to_string())?;
-    Ok(())
+) -> Result<enhancer_optimizer::OptimizeResult, String> {
+    app_state.run_solver(|cancel| {
+        enhancer_optimizer::optimize_enhancers(
+            attributes, recipe, &meals, &medicines, &options, cancel,
+        )
+    })?
+}
+
+/// 释放求解器
+#[tauri::command]
+fn destroy_solver(id: JobId, app_state: tauri::State<'_, AppState>) -> Result<(), String> {
+    app_state.solve_jobs.remove(id)
 }
 
 /// Set the window to Dark mode or Light mode by passing the is_dark argument.
@@ -602,8 +1005,27 @@ fn rand_simulation(
     actions: Vec<Actions>,
     n: usize,
     ignore_errors: bool,
+    seed: Option<u64>,
 ) -> rand_simulations::Statistics {
-    rand_simulations::stat(status, &actions, n, ignore_errors)
+    rand_simulations::stat(status, &actions, n, ignore_errors, seed)
+}
+
+#[tauri::command(async)]
+fn rand_rotation_simulation(
+    status: Status,
+    rotation: String,
+    n: usize,
+    ignore_errors: bool,
+    seed: Option<u64>,
+) -> Result<rand_simulations::Statistics, String> {
+    let rotation: Rotation = rotation.parse().map_err(err_to_string)?;
+    Ok(rand_simulations::stat_rotation(
+        status,
+        &rotation,
+        n,
+        ignore_errors,
+        seed,
+    ))
 }
 
 #[tauri::command(async)]
@@ -612,14 +1034,67 @@ fn rand_collectables_simulation(
     actions: Vec<Actions>,
     n: usize,
     ignore_errors: bool,
-    collectables_shop_refine: rand_simulations::CollectablesShopRefine,
+    collectables_metadata: CollectablesMetadata,
+    seed: Option<u64>,
 ) -> rand_simulations::CollectableStatistics {
     rand_simulations::stat_collectables(
         status,
         &actions,
         n,
         ignore_errors,
-        collectables_shop_refine,
+        collectables_metadata,
+        seed,
+    )
+}
+
+#[tauri::command(async)]
+fn exact_distribution(
+    status: Status,
+    actions: Vec<Actions>,
+    ignore_errors: bool,
+    collectables_shop_refine: Option<rand_simulations::CollectablesShopRefine>,
+) -> exact_distribution::Distribution {
+    exact_distribution::exact_distribution(
+        status,
+        &Rotation::from(actions.as_slice()),
+        ignore_errors,
+        collectables_shop_refine.as_ref(),
+    )
+}
+
+#[tauri::command(async)]
+fn feasibility_map(
+    status: Status,
+    actions: Vec<Actions>,
+    craftsmanship: feasibility_map::Axis,
+    control: feasibility_map::Axis,
+    craft_points: Option<i32>,
+    collectables_shop_refine: Option<rand_simulations::CollectablesShopRefine>,
+) -> feasibility_map::FeasibilityMap {
+    feasibility_map::feasibility_map(
+        status,
+        &actions,
+        craftsmanship,
+        control,
+        craft_points,
+        collectables_shop_refine.as_ref(),
+    )
+}
+
+#[tauri::command(async)]
+fn stat_weights(
+    status: Status,
+    actions: Vec<Actions>,
+    delta: i32,
+    ignore_errors: bool,
+    collectables_shop_refine: Option<rand_simulations::CollectablesShopRefine>,
+) -> stat_weights::StatWeights {
+    stat_weights::stat_weights(
+        status,
+        stat_weights::Plan::Rotation(&Rotation::from(actions.as_slice())),
+        delta,
+        ignore_errors,
+        collectables_shop_refine.as_ref(),
     )
 }
 
@@ -639,30 +1114,52 @@ fn main() {
             recipe_level_table,
             new_status,
             simulate,
+            simulate_trace,
+            simulate_scripted,
             simulate_one_step,
+            simulate_rotation,
+            simulate_rotation_one_step,
+            parse_macro,
+            generate_macro,
             high_quality_probability,
+            initial_quality,
+            required_hq_ingredients,
             allowed_list,
             craftpoints_list,
             recipe_table,
+            recipe_info,
+            recipe,
             recipes_ingredientions,
+            recipes_using_item,
+            bom,
             recipe_collectability,
             item_info,
             craft_type,
             medicine_table,
             meals_table,
+            apply_enhancers,
             create_solver,
             read_solver,
             destroy_solver,
-            rika_solve,
-            rika_solve_tnzever,
-            dfs_solve,
-            nq_solve,
-            reflect_solve,
-            raphael_solve,
+            solver_list,
+            solve,
+            solve_portfolio,
+            cancel_solve,
+            start_solve,
+            poll_solve,
+            cancel_solve_job,
+            solve_result,
+            set_max_concurrent_solves,
+            expert_solve,
+            optimize_enhancers,
             set_theme,
             rand_simulation,
+            rand_rotation_simulation,
             rand_collectables_simulation,
+            exact_distribution,
             calc_attributes_scope,
+            feasibility_map,
+            stat_weights,
         ])
         .setup(|app| {
             let window = app.get_webview_window("main").unwrap();
@@ -0,0 +1,112 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use app_libs::recipe::{RecipeFilter, RecipeOrder, NORMAL_CONDITIONS_FLAG};
+use sea_orm::{
+    query::*,
+    sea_query::{Expr, SimpleExpr},
+    ColumnTrait, Select,
+};
+
+use crate::db::{recipe_level_tables, recipes};
+
+/// 把[`RecipeFilter`]转换为本地数据库的查询条件
+pub trait ApplyRecipeFilter {
+    /// 为查询添加筛选条件和排序，查询中需要已经连接了`RecipeLevelTables`表
+    fn apply(self, query: Select<recipes::Entity>) -> Select<recipes::Entity>;
+}
+
+impl ApplyRecipeFilter for RecipeFilter<i32> {
+    fn apply(self, mut query: Select<recipes::Entity>) -> Select<recipes::Entity> {
+        if let Some(can_hq) = self.can_hq {
+            query = query.filter(recipes::Column::CanHq.eq(can_hq))
+        }
+        if let Some(collectable) = self.collectable {
+            let key = recipes::Column::CollectablesMetadataKey;
+            query = query.filter(if collectable { key.ne(0) } else { key.eq(0) })
+        }
+        if let Some(expert) = self.expert {
+            let flag = recipe_level_tables::Column::ConditionsFlag;
+            query = query.filter(if expert {
+                flag.ne(i32::from(NORMAL_CONDITIONS_FLAG))
+            } else {
+                flag.eq(i32::from(NORMAL_CONDITIONS_FLAG))
+            })
+        }
+        let ranges = [
+            (
+                Expr::col((recipes::Entity, recipes::Column::RequiredCraftsmanship)).into(),
+                self.craftsmanship_min,
+                self.craftsmanship_max,
+            ),
+            (
+                Expr::col((recipes::Entity, recipes::Column::RequiredControl)).into(),
+                self.control_min,
+                self.control_max,
+            ),
+            (
+                factored(
+                    recipe_level_tables::Column::Durability,
+                    recipes::Column::DurabilityFactor,
+                ),
+                self.durability_min,
+                self.durability_max,
+            ),
+        ];
+        for (expr, min, max) in ranges {
+            if let Some(min) = min {
+                query = query.filter(Expr::expr(expr.clone()).gte(min))
+            }
+            if let Some(max) = max {
+                query = query.filter(Expr::expr(expr).lte(max))
+            }
+        }
+
+        let order = if self.descending {
+            Order::Desc
+        } else {
+            Order::Asc
+        };
+        query = match self.order_by {
+            RecipeOrder::Id => return query.order_by(recipes::Column::Id, order),
+            RecipeOrder::Level => query
+                .order_by(recipe_level_tables::Column::ClassJobLevel, order.clone())
+                .order_by(recipes::Column::RecipeLevelId, order),
+            RecipeOrder::Difficulty => query.order_by(
+                factored(
+                    recipe_level_tables::Column::Difficulty,
+                    recipes::Column::DifficultyFactor,
+                ),
+                order,
+            ),
+            RecipeOrder::Quality => query.order_by(
+                factored(
+                    recipe_level_tables::Column::Quality,
+                    recipes::Column::QualityFactor,
+                ),
+                order,
+            ),
+        };
+        query.order_by(recipes::Column::Id, Order::Asc)
+    }
+}
+
+/// 配方等级表中的数值乘以配方中以百分比表示的系数
+fn factored(base: recipe_level_tables::Column, factor: recipes::Column) -> SimpleExpr {
+    Expr::col((recipe_level_tables::Entity, base))
+        .mul(Expr::col((recipes::Entity, factor)))
+        .div(100)
+}
@@ -0,0 +1,510 @@
+// This file is part of BestCraft.
+// Copyright (C) 2025 Tnze
+//
+// BestCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// BestCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use app_libs::{
+    ffxiv_crafting::{Actions, Status},
+    solver::{
+        muscle_memory_solver, reflect_solver,
+        registry::{self, SolverConfig},
+        CancelToken, ProgressSink, Solver, SolverHash,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+pub type JobId = u64;
+
+/// 已结束的任务在没有被取走结果时保留的时间，超时后会在提交新任务时被清除
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// 需要预先建表的动态规划求解器的参数。
+/// 建好的求解器可以从同一配方的任意状态读取结果，直到被释放为止
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct PrepareConfig {
+    pub use_muscle_memory: bool,
+    pub use_manipulation: bool,
+    pub use_observe: bool,
+}
+
+impl PrepareConfig {
+    fn build(self, status: Status, cancel: CancelToken) -> Box<dyn Solver + Send> {
+        if self.use_muscle_memory {
+            Box::new(muscle_memory_solver::PreprogressSolver::new(
+                status,
+                self.use_manipulation,
+                8,
+                self.use_observe,
+                cancel,
+            ))
+        } else {
+            Box::new(reflect_solver::QualitySolver::new(
+                status,
+                self.use_manipulation,
+                8 + 1,
+                self.use_observe,
+                cancel,
+            ))
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Finished,
+    Cancelled,
+}
+
+/// `poll_solve`返回的任务状态
+#[derive(Serialize, Debug, Clone)]
+pub struct JobStatus {
+    pub state: JobState,
+    /// 求解器报告的进度，单位由求解器决定
+    pub progress: usize,
+    /// 求解过程中找到的最新的中间结果
+    pub intermediate: Option<Vec<Actions>>,
+    /// 任务开始运行后经过的毫秒数，排队中的任务为`None`
+    pub elapsed: Option<u64>,
+}
+
+#[derive(Default)]
+struct JobProgress {
+    progress: AtomicUsize,
+    intermediate: Mutex<Option<Vec<Actions>>>,
+}
+
+impl ProgressSink for JobProgress {
+    fn progress(&self, done: usize) {
+        self.progress.store(done, Ordering::Relaxed);
+    }
+
+    fn intermediate(&self, actions: &[Actions]) {
+        *self.intermediate.lock().unwrap() = Some(actions.to_vec());
+    }
+}
+
+#[derive(Serialize)]
+enum Task {
+    Solve(SolverConfig),
+    Prepare(PrepareConfig),
+}
+
+type PreparedSolver = Arc<Mutex<Box<dyn Solver + Send>>>;
+
+enum Output {
+    Actions(Vec<Actions>),
+    /// 建好的求解器及其对应的属性和配方
+    Prepared(SolverHash, PreparedSolver),
+}
+
+enum Stage {
+    Queued(Status, Task),
+    Running(Instant),
+    Finished(Output, Duration),
+    Cancelled,
+}
+
+struct Job {
+    // 由状态和求解器配置序列化得到，用于合并重复提交的任务
+    key: String,
+    cancel: CancelToken,
+    progress: Arc<JobProgress>,
+    stage: Stage,
+    /// 任务结束的时间
+    ended: Option<Instant>,
+}
+
+impl Job {
+    /// 已结束并超过保留时间的任务，建好的求解器需要手动释放，不会过期
+    fn expired(&self) -> bool {
+        !matches!(self.stage, Stage::Finished(Output::Prepared(..), _))
+            && self
+                .ended
+                .is_some_and(|ended| ended.elapsed() >= FINISHED_JOB_TTL)
+    }
+}
+
+struct Jobs {
+    next_id: JobId,
+    max_running: usize,
+    running: usize,
+    queue: VecDeque<JobId>,
+    jobs: HashMap<JobId, Job>,
+}
+
+/// 管理所有求解任务。任务在后台线程中运行，同时运行的任务数不超过设定的上限，其余任务排队等待
+#[derive(Clone)]
+pub struct SolveJobs(Arc<Mutex<Jobs>>);
+
+impl SolveJobs {
+    pub fn new(max_running: usize) -> Self {
+        Self(Arc::new(Mutex::new(Jobs {
+            next_id: 0,
+            max_running: max_running.max(1),
+            running: 0,
+            queue: VecDeque::new(),
+            jobs: HashMap::new(),
+        })))
+    }
+
+    /// 提交一个求解任务。已有相同状态和配置的任务尚未结束时，直接返回该任务的ID
+    pub fn start(&self, status: Status, config: SolverConfig) -> Result<JobId, String> {
+        config.check(&status)?;
+        self.submit(status, Task::Solve(config))
+    }
+
+    /// 提交一个建表任务，任务结束后用[`SolveJobs::read`]读取结果，用[`SolveJobs::remove`]释放
+    pub fn prepare(&self, status: Status, config: PrepareConfig) -> Result<JobId, String> {
+        self.submit(status, Task::Prepare(config))
+    }
+
+    fn submit(&self, status: Status, task: Task) -> Result<JobId, String> {
+        let key = serde_json::to_string(&(&status, &task)).map_err(|e| e.to_string())?;
+        let mut jobs = self.0.lock().unwrap();
+        jobs.jobs.retain(|_, job| !job.expired());
+        // 已被取消但仍在运行的任务不会产生结果，不能合并
+        let existing = jobs.jobs.iter().find(|(_, job)| {
+            job.key == key
+                && matches!(job.stage, Stage::Queued(..) | Stage::Running(_))
+                && !job.cancel.is_cancelled()
+        });
+        if let Some((&id, _)) = existing {
+            return Ok(id);
+        }
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        jobs.jobs.insert(
+            id,
+            Job {
+                key,
+                cancel: CancelToken::new(),
+                progress: Arc::default(),
+                stage: Stage::Queued(status, task),
+                ended: None,
+            },
+        );
+        jobs.queue.push_back(id);
+        drop(jobs);
+        self.schedule();
+        Ok(id)
+    }
+
+    pub fn poll(&self, id: JobId) -> Result<JobStatus, String> {
+        let jobs = self.0.lock().unwrap();
+        let job = jobs.jobs.get(&id).ok_or("job-doesn-t-exist")?;
+        let intermediate = job.progress.intermediate.lock().unwrap().clone();
+        let (state, elapsed) = match &job.stage {
+            Stage::Queued(..) => (JobState::Queued, None),
+            Stage::Running(start) => (JobState::Running, Some(start.elapsed())),
+            Stage::Finished(_, elapsed) => (JobState::Finished, Some(*elapsed)),
+            Stage::Cancelled => (JobState::Cancelled, None),
+        };
+        Ok(JobStatus {
+            state,
+            progress: job.progress.progress.load(Ordering::Relaxed),
+            intermediate,
+            elapsed: elapsed.map(|d| d.as_millis() as u64),
+        })
+    }
+
+    /// 中止任务，排队中的任务会被立即移出队列
+    pub fn cancel(&self, id: JobId) -> Result<(), String> {
+        let mut jobs = self.0.lock().unwrap();
+        let job = jobs.jobs.get_mut(&id).ok_or("job-doesn-t-exist")?;
+        job.cancel.cancel();
+        if let Stage::Queued(..) = job.stage {
+            job.stage = Stage::Cancelled;
+            job.ended = Some(Instant::now());
+            jobs.queue.retain(|&queued| queued != id);
+        }
+        Ok(())
+    }
+
+    /// 中止所有未结束的任务，建好的求解器不受影响
+    pub fn cancel_all(&self) {
+        let jobs = self.0.lock().unwrap();
+        let ids: Vec<_> = jobs
+            .jobs
+            .iter()
+            .filter(|(_, job)| matches!(job.stage, Stage::Queued(..) | Stage::Running(_)))
+            .map(|(&id, _)| id)
+            .collect();
+        drop(jobs);
+        for id in ids {
+            let _ = self.cancel(id);
+        }
+    }
+
+    /// 取出已结束任务的结果，之后该任务会被移除
+    pub fn result(&self, id: JobId) -> Result<Vec<Actions>, String> {
+        let mut jobs = self.0.lock().unwrap();
+        let job = jobs.jobs.get(&id).ok_or("job-doesn-t-exist")?;
+        match job.stage {
+            Stage::Queued(..) | Stage::Running(_) => return Err("job-isn-t-finished".into()),
+            Stage::Finished(Output::Prepared(..), _) => return Err("job-is-a-solver".into()),
+            Stage::Finished(Output::Actions(_), _) | Stage::Cancelled => {}
+        }
+        match jobs.jobs.remove(&id).unwrap().stage {
+            Stage::Finished(Output::Actions(actions), _) => Ok(actions),
+            _ => Err("solve-cancelled".into()),
+        }
+    }
+
+    /// 从建好的求解器中读取`status`之后的技能，`status`需要与建表时的属性和配方相同
+    pub fn read(&self, id: JobId, status: &Status) -> Result<Vec<Actions>, String> {
+        let (solver, cancel) = {
+            let jobs = self.0.lock().unwrap();
+            let job = jobs.jobs.get(&id).ok_or("job-doesn-t-exist")?;
+            let Stage::Finished(Output::Prepared(key, solver), _) = &job.stage else {
+                return Err("solver-isn-t-prepared".into());
+            };
+            if key.attributes != status.attributes || key.recipe != status.recipe {
+                return Err("solver-doesn-t-exist".into());
+            }
+            (solver.clone(), job.cancel.clone())
+        };
+        let result = solver.lock().unwrap().read_all(status);
+        if cancel.is_cancelled() {
+            // 被取消的求解器中的表格不再可靠，直接释放
+            self.0.lock().unwrap().jobs.remove(&id);
+            return Err("solve-cancelled".into());
+        }
+        Ok(result)
+    }
+
+    /// 中止并移除任务，用于释放建好的求解器
+    pub fn remove(&self, id: JobId) -> Result<(), String> {
+        let mut jobs = self.0.lock().unwrap();
+        let job = jobs.jobs.remove(&id).ok_or("job-doesn-t-exist")?;
+        job.cancel.cancel();
+        jobs.queue.retain(|&queued| queued != id);
+        Ok(())
+    }
+
+    pub fn set_max_running(&self, max_running: usize) {
+        self.0.lock().unwrap().max_running = max_running.max(1);
+        self.schedule();
+    }
+
+    /// 在不超过上限的前提下启动排队中的任务
+    fn schedule(&self) {
+        let mut jobs = self.0.lock().unwrap();
+        while jobs.running < jobs.max_running {
+            let Some(id) = jobs.queue.pop_front() else {
+                break;
+            };
+            let job = jobs.jobs.get_mut(&id).unwrap();
+            let Stage::Queued(status, task) =
+                std::mem::replace(&mut job.stage, Stage::Running(Instant::now()))
+            else {
+                continue;
+            };
+            let (cancel, progress) = (job.cancel.clone(), job.progress.clone());
+            jobs.running += 1;
+
+            let this = self.clone();
+            std::thread::spawn(move || {
+                let output = match task {
+                    // 已在提交时检查过配置，此处不会出错
+                    Task::Solve(config) => Output::Actions(
+                        registry::solve(&config, status, &cancel, progress.as_ref())
+                            .unwrap_or_default(),
+                    ),
+                    Task::Prepare(config) => {
+                        let key = SolverHash {
+                            attributes: status.attributes,
+                            recipe: status.recipe,
+                        };
+                        let solver = config.build(status, cancel.clone());
+                        Output::Prepared(key, Arc::new(Mutex::new(solver)))
+                    }
+                };
+                let mut jobs = this.0.lock().unwrap();
+                jobs.running -= 1;
+                // 任务可能在运行期间被取消
+                if let Some(job) = jobs.jobs.get_mut(&id) {
+                    job.stage = match job.stage {
+                        _ if cancel.is_cancelled() => Stage::Cancelled,
+                        Stage::Running(start) => Stage::Finished(output, start.elapsed()),
+                        _ => unreachable!(),
+                    };
+                    job.ended = Some(Instant::now());
+                }
+                drop(jobs);
+                this.schedule();
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use app_libs::{
+        ffxiv_crafting::{data, Actions, Attributes, Recipe, Status},
+        solver::{registry::SolverConfig, CancelToken, Solver, SolverHash},
+    };
+
+    use super::{Job, JobId, JobState, Output, SolveJobs, Stage, Task};
+
+    fn status() -> Status {
+        let recipe = Recipe {
+            rlv: data::recipe_level_table(620),
+            job_level: 90,
+            difficulty: 5720,
+            quality: 12900,
+            durability: 70,
+            conditions_flag: 15,
+        };
+        let attributes = Attributes {
+            level: 90,
+            craftsmanship: 4214,
+            control: 3528,
+            craft_points: 691,
+        };
+        Status::new(attributes, recipe)
+    }
+
+    fn config() -> SolverConfig {
+        SolverConfig::DepthFirstSearch {
+            depth: 1,
+            specialist: false,
+        }
+    }
+
+    /// 不经过调度直接放入一个任务，处于运行中的任务会占用一个名额
+    fn insert(jobs: &SolveJobs, key: String, stage: Stage) -> JobId {
+        let mut jobs = jobs.0.lock().unwrap();
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        if let Stage::Running(_) = stage {
+            jobs.running += 1;
+        }
+        let job = Job {
+            key,
+            cancel: CancelToken::new(),
+            progress: Arc::default(),
+            stage,
+            ended: None,
+        };
+        jobs.jobs.insert(id, job);
+        id
+    }
+
+    struct Empty;
+
+    impl Solver for Empty {
+        fn init(&mut self) {}
+
+        fn read(&self, _s: &Status) -> Option<Actions> {
+            None
+        }
+    }
+
+    fn prepared(status: &Status) -> Stage {
+        let key = SolverHash {
+            attributes: status.attributes,
+            recipe: status.recipe,
+        };
+        let solver: Box<dyn Solver + Send> = Box::new(Empty);
+        Stage::Finished(
+            Output::Prepared(key, Arc::new(Mutex::new(solver))),
+            Duration::ZERO,
+        )
+    }
+
+    #[test]
+    fn jobs_are_queued_beyond_max_running() {
+        let jobs = SolveJobs::new(1);
+        insert(&jobs, String::new(), Stage::Running(Instant::now()));
+        let id = jobs.start(status(), config()).unwrap();
+        assert_eq!(jobs.poll(id).unwrap().state, JobState::Queued);
+
+        jobs.set_max_running(2);
+        assert_ne!(jobs.poll(id).unwrap().state, JobState::Queued);
+        jobs.cancel(id).unwrap();
+    }
+
+    #[test]
+    fn duplicate_jobs_are_merged_unless_cancelled() {
+        let jobs = SolveJobs::new(1);
+        let key = serde_json::to_string(&(&status(), &Task::Solve(config()))).unwrap();
+        let running = insert(&jobs, key, Stage::Running(Instant::now()));
+        assert_eq!(jobs.start(status(), config()).unwrap(), running);
+
+        // 运行中的任务被取消后不再合并，新任务需要排队
+        jobs.cancel(running).unwrap();
+        let queued = jobs.start(status(), config()).unwrap();
+        assert_ne!(queued, running);
+        assert_eq!(jobs.start(status(), config()).unwrap(), queued);
+
+        jobs.cancel(queued).unwrap();
+        assert_eq!(jobs.poll(queued).unwrap().state, JobState::Cancelled);
+        assert_ne!(jobs.start(status(), config()).unwrap(), queued);
+    }
+
+    #[test]
+    fn result_removes_job() {
+        let jobs = SolveJobs::new(1);
+        let stage = Stage::Finished(
+            Output::Actions(vec![Actions::BasicSynthesis]),
+            Duration::ZERO,
+        );
+        let id = insert(&jobs, String::new(), stage);
+        assert_eq!(jobs.result(id).unwrap(), [Actions::BasicSynthesis]);
+        assert_eq!(jobs.result(id).unwrap_err(), "job-doesn-t-exist");
+
+        let solver = insert(&jobs, String::new(), prepared(&status()));
+        assert_eq!(jobs.result(solver).unwrap_err(), "job-is-a-solver");
+    }
+
+    #[test]
+    fn read_requires_same_attributes() {
+        let jobs = SolveJobs::new(1);
+        let status = status();
+        let id = insert(&jobs, String::new(), prepared(&status));
+        assert_eq!(jobs.read(id, &status).unwrap(), []);
+
+        let mut other = status.clone();
+        other.attributes.control += 1;
+        assert_eq!(jobs.read(id, &other).unwrap_err(), "solver-doesn-t-exist");
+    }
+
+    #[test]
+    fn cancel_all_keeps_prepared_solvers() {
+        let jobs = SolveJobs::new(1);
+        let status = status();
+        let solver = insert(&jobs, String::new(), prepared(&status));
+        let running = insert(&jobs, String::new(), Stage::Running(Instant::now()));
+        jobs.cancel_all();
+        assert!(jobs.0.lock().unwrap().jobs[&running].cancel.is_cancelled());
+        assert_eq!(jobs.read(solver, &status).unwrap(), []);
+    }
+}
@@ -16,10 +16,12 @@
 
 use app_libs::{
     ffxiv_crafting::{Actions, Attributes, Recipe, Status},
+    macros::GenerateOptions,
+    rotation::Rotation,
+    trace::ScriptedStep,
     SimulateOneStepResult,
 };
 
-use rand::rng;
 use serde_wasm_bindgen::{from_value, to_value};
 use wasm_bindgen::prelude::*;
 
@@ -42,16 +44,68 @@ pub fn simulate(status: JsValue, actions: JsValue) -> Result<JsValue, JsValue> {
     Ok(to_value(&app_libs::simulate(status, actions))?)
 }
 
+#[wasm_bindgen]
+pub fn simulate_trace(status: JsValue, actions: JsValue) -> Result<JsValue, JsValue> {
+    let status: Status = from_value(status)?;
+    let actions: Vec<Actions> = from_value(actions)?;
+    Ok(to_value(&app_libs::trace::simulate_trace(status, actions))?)
+}
+
+#[wasm_bindgen]
+pub fn simulate_scripted(status: JsValue, script: JsValue) -> Result<JsValue, JsValue> {
+    let status: Status = from_value(status)?;
+    let script: Vec<ScriptedStep> = from_value(script)?;
+    let result = app_libs::trace::simulate_scripted(status, script)?;
+    Ok(to_value(&result)?)
+}
+
+#[wasm_bindgen]
+pub fn simulate_rotation(status: JsValue, rotation: &str) -> Result<JsValue, JsValue> {
+    let status: Status = from_value(status)?;
+    let rotation: Rotation = rotation.parse().map_err(err_to_string)?;
+    Ok(to_value(&app_libs::simulate_rotation(status, &rotation))?)
+}
+
+/// wasm-bindgen会把`u64`转换为JS中的BigInt，因此这里的种子都使用`u32`，可以直接传入普通的数字
+#[wasm_bindgen]
+pub fn simulate_rotation_one_step(
+    status: JsValue,
+    rotation: &str,
+    pc: usize,
+    force_success: bool,
+    seed: Option<u32>,
+) -> Result<JsValue, JsValue> {
+    let status: Status = from_value(status)?;
+    let rotation: Rotation = rotation.parse().map_err(err_to_string)?;
+    let mut rng = app_libs::new_step_rng(seed.map(u64::from), status.step);
+    let result = app_libs::simulate_rotation_step(status, &rotation, pc, force_success, &mut rng);
+    Ok(to_value(&result)?)
+}
+
+#[wasm_bindgen]
+pub fn parse_macro(text: &str) -> Result<JsValue, JsValue> {
+    Ok(to_value(&app_libs::macros::parse(text))?)
+}
+
+#[wasm_bindgen]
+pub fn generate_macro(actions: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let actions: Vec<Actions> = from_value(actions)?;
+    let options: GenerateOptions = from_value(options)?;
+    Ok(to_value(&app_libs::macros::generate(&actions, &options))?)
+}
+
 #[wasm_bindgen]
 pub fn simulate_one_step(
     status: JsValue,
     action: JsValue,
     force_success: JsValue,
+    seed: Option<u32>,
 ) -> Result<JsValue, JsValue> {
     let mut status: Status = from_value(status)?;
     let action: Actions = from_value(action)?;
     let force_success: bool = from_value(force_success)?;
-    let is_success = app_libs::simulate_one_step(&mut status, action, force_success, &mut rng())
+    let mut rng = app_libs::new_step_rng(seed.map(u64::from), status.step);
+    let is_success = app_libs::simulate_one_step(&mut status, action, force_success, &mut rng)
         .map_err(err_to_string)?;
     Ok(to_value(&SimulateOneStepResult { status, is_success })?)
 }
@@ -77,62 +131,113 @@ pub fn high_quality_probability(status: JsValue) -> Result<JsValue, JsValue> {
 }
 
 #[wasm_bindgen]
-pub fn rika_solve(status: JsValue) -> Result<JsValue, JsValue> {
-    use app_libs::solver::rika_solver::solve;
+pub fn initial_quality(
+    recipe: JsValue,
+    material_quality_factor: u32,
+    ingredients: JsValue,
+) -> Result<u32, JsValue> {
+    use app_libs::recipe::Ingredient;
+    let recipe: Recipe = from_value(recipe)?;
+    let ingredients: Vec<Ingredient> = from_value(ingredients)?;
+    Ok(app_libs::recipe::initial_quality(
+        &recipe,
+        material_quality_factor,
+        &ingredients,
+    ))
+}
+
+#[wasm_bindgen]
+pub fn required_hq_ingredients(
+    status: JsValue,
+    actions: JsValue,
+    material_quality_factor: u32,
+    ingredients: JsValue,
+) -> Result<JsValue, JsValue> {
+    use app_libs::recipe::Ingredient;
     let status: Status = from_value(status)?;
-    let result: Vec<Actions> = solve(status);
+    let actions: Vec<Actions> = from_value(actions)?;
+    let ingredients: Vec<Ingredient> = from_value(ingredients)?;
+    let result = app_libs::recipe::required_hq_ingredients(
+        &status,
+        &actions,
+        material_quality_factor,
+        &ingredients,
+    );
     Ok(to_value(&result)?)
 }
 
 #[wasm_bindgen]
-pub fn dfs_solve(status: JsValue, depth: usize, specialist: bool) -> Result<JsValue, JsValue> {
-    use app_libs::solver::depth_first_search_solver::solve;
-    let status: Status = from_value(status)?;
-    let result: Vec<Actions> = solve(status, depth, specialist);
+pub fn apply_enhancers(attributes: JsValue, enhancers: JsValue) -> Result<JsValue, JsValue> {
+    use app_libs::enhancer::Enhancer;
+    let attributes: Attributes = from_value(attributes)?;
+    let enhancers: Vec<Enhancer> = from_value(enhancers)?;
+    let result = app_libs::enhancer::apply_enhancers(attributes, &enhancers);
     Ok(to_value(&result)?)
 }
 
 #[wasm_bindgen]
-pub fn nq_solve(status: JsValue, depth: usize, specialist: bool) -> Result<JsValue, JsValue> {
-    use app_libs::solver::normal_progress_solver::solve;
+pub fn solver_list() -> Result<JsValue, JsValue> {
+    use app_libs::solver::registry::SOLVERS;
+    Ok(to_value(&SOLVERS)?)
+}
+
+#[wasm_bindgen]
+pub fn solve(status: JsValue, config: JsValue) -> Result<JsValue, JsValue> {
+    use app_libs::solver::{
+        registry::{solve, SolverConfig},
+        CancelToken,
+    };
     let status: Status = from_value(status)?;
-    let result: Vec<Actions> = solve(status, depth, specialist);
+    let config: SolverConfig = from_value(config)?;
+    // Worker会在取消时被直接终止，这里不需要取消标记和进度
+    let result: Vec<Actions> = solve(&config, status, &CancelToken::new(), &())?;
     Ok(to_value(&result)?)
 }
 
+/// 高难度配方的逐步求解，每步只给出一个技能，因此不经过[`solve`]
 #[wasm_bindgen]
-pub fn reflect_solve(status: JsValue, use_observe: bool) -> Result<JsValue, JsValue> {
-    use app_libs::solver::reflect_solver::solve;
+pub fn expert_solve(
+    status: JsValue,
+    depth: usize,
+    specialist: bool,
+    objective: JsValue,
+) -> Result<JsValue, JsValue> {
+    use app_libs::solver::{
+        expert_solver::{ExpertSolver, Objective},
+        CancelToken,
+    };
     let status: Status = from_value(status)?;
-    let result: Vec<Actions> = solve(status.clone(), false, 0, use_observe);
+    let objective: Objective = from_value(objective)?;
+    // Worker会在取消时被直接终止
+    let result =
+        ExpertSolver::new(depth, specialist, objective, CancelToken::new()).suggest(&status);
     Ok(to_value(&result)?)
 }
 
 #[wasm_bindgen]
-pub fn raphael_solve(
-    status: JsValue,
-    target_quality: Option<u32>,
-    use_manipultaion: bool,
-    use_heart_and_soul: bool,
-    use_quick_innovation: bool,
-    use_trained_eye: bool,
-    backload_progress: bool,
-    adversarial: bool,
-    unsound_branch_pruning: bool,
+pub fn optimize_enhancers(
+    attributes: JsValue,
+    recipe: JsValue,
+    meals: JsValue,
+    medicines: JsValue,
+    options: JsValue,
 ) -> Result<JsValue, JsValue> {
-    use app_libs::solver::raphael::solve;
-    let status: Status = from_value(status)?;
-    let result: Vec<Actions> = solve(
-        status.clone(),
-        target_quality,
-        use_manipultaion,
-        use_heart_and_soul,
-        use_quick_innovation,
-        use_trained_eye,
-        backload_progress,
-        adversarial,
-        unsound_branch_pruning,
-    );
+    use app_libs::{analyzer::enhancer_optimizer::*, enhancer::Enhancer, solver::CancelToken};
+    let attributes: Attributes = from_value(attributes)?;
+    let recipe: Recipe = from_value(recipe)?;
+    let meals: Vec<Enhancer> = from_value(meals)?;
+    let medicines: Vec<Enhancer> = from_value(medicines)?;
+    let mut options: OptimizeOptions = from_value(options)?;
+    // wasm中无法计时，只限制求解次数
+    options.budget.time_limit_ms = None;
+    let result = optimize_enhancers(
+        attributes,
+        recipe,
+        &meals,
+        &medicines,
+        &options,
+        &CancelToken::new(),
+    )?;
     Ok(to_value(&result)?)
 }
 
@@ -142,10 +247,37 @@ pub fn rand_simulation(
     actions: JsValue,
     n: usize,
     ignore_errors: bool,
+    seed: Option<u32>,
 ) -> Result<JsValue, JsValue> {
     let status: Status = from_value(status)?;
     let actions: Vec<Actions> = from_value(actions)?;
-    let result = app_libs::analyzer::rand_simulations::stat(status, &actions, n, ignore_errors);
+    let result = app_libs::analyzer::rand_simulations::stat(
+        status,
+        &actions,
+        n,
+        ignore_errors,
+        seed.map(u64::from),
+    );
+    Ok(to_value(&result)?)
+}
+
+#[wasm_bindgen]
+pub fn rand_rotation_simulation(
+    status: JsValue,
+    rotation: &str,
+    n: usize,
+    ignore_errors: bool,
+    seed: Option<u32>,
+) -> Result<JsValue, JsValue> {
+    let status: Status = from_value(status)?;
+    let rotation: Rotation = rotation.parse().map_err(err_to_string)?;
+    let result = app_libs::analyzer::rand_simulations::stat_rotation(
+        status,
+        &rotation,
+        n,
+        ignore_errors,
+        seed.map(u64::from),
+    );
     Ok(to_value(&result)?)
 }
 
@@ -155,13 +287,95 @@ pub fn rand_collectables_simulation(
     actions: JsValue,
     n: usize,
     ignore_errors: bool,
+    collectables_metadata: JsValue,
+    seed: Option<u32>,
+) -> Result<JsValue, JsValue> {
+    use app_libs::analyzer::rand_simulations::{stat_collectables, CollectablesMetadata};
+    let status: Status = from_value(status)?;
+    let actions: Vec<Actions> = from_value(actions)?;
+    let collectables_metadata: CollectablesMetadata = from_value(collectables_metadata)?;
+    let result = stat_collectables(
+        status,
+        &actions,
+        n,
+        ignore_errors,
+        collectables_metadata,
+        seed.map(u64::from),
+    );
+    Ok(to_value(&result)?)
+}
+
+#[wasm_bindgen]
+pub fn exact_distribution(
+    status: JsValue,
+    actions: JsValue,
+    ignore_errors: bool,
+    collectables_shop_refine: JsValue,
+) -> Result<JsValue, JsValue> {
+    use app_libs::analyzer::rand_simulations::CollectablesShopRefine;
+    let status: Status = from_value(status)?;
+    let actions: Vec<Actions> = from_value(actions)?;
+    let collectables_shop_refine: Option<CollectablesShopRefine> =
+        from_value(collectables_shop_refine)?;
+    let result = app_libs::analyzer::exact_distribution::exact_distribution(
+        status,
+        &Rotation::from(actions.as_slice()),
+        ignore_errors,
+        collectables_shop_refine.as_ref(),
+    );
+    Ok(to_value(&result)?)
+}
+
+#[wasm_bindgen]
+pub fn feasibility_map(
+    status: JsValue,
+    actions: JsValue,
+    craftsmanship: JsValue,
+    control: JsValue,
+    craft_points: Option<i32>,
+    collectables_shop_refine: JsValue,
+) -> Result<JsValue, JsValue> {
+    use app_libs::analyzer::{feasibility_map::Axis, rand_simulations::CollectablesShopRefine};
+    let status: Status = from_value(status)?;
+    let actions: Vec<Actions> = from_value(actions)?;
+    let craftsmanship: Axis = from_value(craftsmanship)?;
+    let control: Axis = from_value(control)?;
+    let collectables_shop_refine: Option<CollectablesShopRefine> =
+        from_value(collectables_shop_refine)?;
+    let result = app_libs::analyzer::feasibility_map::feasibility_map(
+        status,
+        &actions,
+        craftsmanship,
+        control,
+        craft_points,
+        collectables_shop_refine.as_ref(),
+    );
+    Ok(to_value(&result)?)
+}
+
+#[wasm_bindgen]
+pub fn stat_weights(
+    status: JsValue,
+    actions: JsValue,
+    delta: i32,
+    ignore_errors: bool,
     collectables_shop_refine: JsValue,
 ) -> Result<JsValue, JsValue> {
-    use app_libs::analyzer::rand_simulations::{stat_collectables, CollectablesShopRefine};
+    use app_libs::analyzer::{
+        rand_simulations::CollectablesShopRefine,
+        stat_weights::{stat_weights, Plan},
+    };
     let status: Status = from_value(status)?;
     let actions: Vec<Actions> = from_value(actions)?;
-    let collectables_shop_refine: CollectablesShopRefine = from_value(collectables_shop_refine)?;
-    let result = stat_collectables(status, &actions, n, ignore_errors, collectables_shop_refine);
+    let collectables_shop_refine: Option<CollectablesShopRefine> =
+        from_value(collectables_shop_refine)?;
+    let result = stat_weights(
+        status,
+        Plan::Rotation(&Rotation::from(actions.as_slice())),
+        delta,
+        ignore_errors,
+        collectables_shop_refine.as_ref(),
+    );
     Ok(to_value(&result)?)
 }
 
@@ -15,9 +15,12 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 import {
+    Bom,
     CollectablesShopRefine,
+    IngredientUsage,
     Item,
     ItemWithAmount,
+    Recipe,
     RecipeInfo,
     RecipeLevel,
 } from '@/libs/Craft';
@@ -26,6 +29,7 @@ import {
     CraftType,
     DataSourceResult,
     DataSourceType,
+    RecipeFilter,
     RecipesSourceResult,
 } from './source';
 
@@ -40,6 +44,7 @@ export class LocalRecipeSource {
         craftTypeId?: number,
         jobLevelMin?: number,
         jobLevelMax?: number,
+        filter?: RecipeFilter,
     ): Promise<RecipesSourceResult> {
         if (searchName === undefined) {
             searchName = '';
@@ -53,6 +58,7 @@ export class LocalRecipeSource {
             recipeLevel: rlv,
             jobLevelMin,
             jobLevelMax,
+            filter,
         });
         return { results, totalPages };
     }
@@ -64,6 +70,22 @@ export class LocalRecipeSource {
         return ings.map(x => ({ ingredient_id: x[0], amount: x[1] }));
     }
 
+    async recipesUsingItem(
+        itemId: number,
+        page: number,
+    ): Promise<DataSourceResult<IngredientUsage>> {
+        let [results, totalPages]: [IngredientUsage[], number] = await (
+            await this.invoke
+        )('recipes_using_item', { itemId, pageId: page - 1 });
+        return { results, totalPages };
+    }
+
+    async bom(targets: [number, number][]): Promise<Bom> {
+        return await (
+            await this.invoke
+        )('bom', { targets });
+    }
+
     async recipeCollectableShopRefine(
         recipeId: number,
     ): Promise<CollectablesShopRefine> {
@@ -80,6 +102,18 @@ export class LocalRecipeSource {
         return result;
     }
 
+    async recipeInfo(recipeId: number): Promise<RecipeInfo> {
+        return await (
+            await this.invoke
+        )('recipe_info', { recipeId });
+    }
+
+    async recipe(recipeId: number): Promise<Recipe> {
+        return await (
+            await this.invoke
+        )('recipe', { recipeId });
+    }
+
     async itemInfo(itemId: number): Promise<Item> {
         const { id, name, level, can_be_hq, category_id } = (await (
             await this.invoke
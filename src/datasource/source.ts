@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-import { CollectablesShopRefine, Item, ItemWithAmount, RecipeInfo, RecipeLevel } from '@/libs/Craft';
+import { Bom, CollectablesShopRefine, IngredientUsage, Item, ItemWithAmount, newRecipe, Recipe, RecipeInfo, RecipeLevel } from '@/libs/Craft';
 import { Enhancer } from '@/libs/Enhancer';
 
 export interface DataSource {
@@ -26,11 +26,20 @@ export interface DataSource {
         craftTypeId?: number,
         jobLevelMin?: number,
         jobLevelMax?: number,
+        filter?: RecipeFilter,
     ): Promise<RecipesSourceResult>;
     recipesIngredients(recipeId: number): Promise<ItemWithAmount[]>;
+    // 递归展开目标物品的材料清单，targets为[itemId, amount]
+    bom?(targets: [number, number][]): Promise<Bom>;
+    // 查询使用了该物品作为素材的配方
+    recipesUsingItem?(
+        itemId: number,
+        page: number,
+    ): Promise<DataSourceResult<IngredientUsage>>;
     recipeLevelTable(rlv: number): Promise<RecipeLevel>;
     recipeCollectableShopRefine?(recipeId: number): Promise<CollectablesShopRefine>;
     recipeInfo?(recipeId: number): Promise<RecipeInfo>;
+    recipe?(recipeId: number): Promise<Recipe>;
     itemInfo(id: number): Promise<Item>;
     craftTypeList(): Promise<CraftType[]>;
 
@@ -38,6 +47,44 @@ export interface DataSource {
     mealsTable(page: number): Promise<DataSourceResult<Enhancer>>;
 }
 
+// 优先使用数据源计算的配方，数据源不支持时才用配方等级和系数在前端计算
+export async function loadRecipe(
+    source: DataSource,
+    info: RecipeInfo,
+): Promise<Recipe> {
+    if (source.recipe != undefined) {
+        try {
+            return await source.recipe(info.id);
+        } catch (e: any) {
+            // 较旧的服务端没有该接口
+            console.error('Failed to fetch recipe', e);
+        }
+    }
+    return newRecipe(
+        await source.recipeLevelTable(info.rlv),
+        info.difficulty_factor,
+        info.quality_factor,
+        info.durability_factor,
+    );
+}
+
+// 配方列表的附加筛选条件和排序方式，只有部分数据源支持
+export interface RecipeFilter {
+    can_hq?: boolean;
+    collectable?: boolean;
+    // 是否为高难度配方
+    expert?: boolean;
+    craftsmanship_min?: number;
+    craftsmanship_max?: number;
+    control_min?: number;
+    control_max?: number;
+    // 乘以系数后的耐久
+    durability_min?: number;
+    durability_max?: number;
+    order_by?: 'id' | 'level' | 'difficulty' | 'quality';
+    descending?: boolean;
+}
+
 export interface CraftType {
     id: number;
     name: string;
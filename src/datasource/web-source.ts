@@ -15,9 +15,12 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 import {
+    Bom,
     CollectablesShopRefine,
+    IngredientUsage,
     Item,
     ItemWithAmount,
+    Recipe,
     RecipeInfo,
     RecipeLevel,
 } from '@/libs/Craft';
@@ -25,6 +28,7 @@ import {
     CraftType,
     DataSourceResult,
     DataSourceType,
+    RecipeFilter,
     RecipesSourceResult,
 } from './source';
 import { Enhancer } from '@/libs/Enhancer';
@@ -46,6 +50,7 @@ export class WebSource {
         craftTypeId?: number,
         jobLevelMin?: number,
         jobLevelMax?: number,
+        filter?: RecipeFilter,
     ): Promise<RecipesSourceResult> {
         if (searchName === undefined) {
             searchName = '';
@@ -66,6 +71,11 @@ export class WebSource {
         if (jobLevelMax !== undefined) {
             query.set('job_level_max', String(jobLevelMax));
         }
+        for (const [key, value] of Object.entries(filter ?? {})) {
+            if (value !== undefined) {
+                query.set(key, String(value));
+            }
+        }
 
         const url =
             new URL('recipe_table', this.base).toString() +
@@ -94,6 +104,43 @@ export class WebSource {
         return ings.map(x => ({ ingredient_id: x[0], amount: x[1] }));
     }
 
+    async recipesUsingItem(
+        itemId: number,
+        page: number,
+    ): Promise<DataSourceResult<IngredientUsage>> {
+        const query = new URLSearchParams({
+            item_id: String(itemId),
+            page_id: String(page - 1),
+        });
+        const url = new URL('recipes_using_item', this.base);
+        url.search = query.toString();
+        const resp = await fetch(url, {
+            method: 'GET',
+            mode: 'cors',
+        });
+        const { data: results, p: totalPages } = (await resp.json()) as {
+            data: IngredientUsage[];
+            p: number;
+        };
+        return { results, totalPages };
+    }
+
+    async bom(targets: [number, number][]): Promise<Bom> {
+        const query = new URLSearchParams({
+            targets: targets.map(([id, amount]) => `${id}:${amount}`).join(','),
+        });
+        const url = new URL('bom', this.base);
+        url.search = query.toString();
+        const resp = await fetch(url, {
+            method: 'GET',
+            mode: 'cors',
+        });
+        if (!resp.ok) {
+            throw resp.statusText;
+        }
+        return resp.json();
+    }
+
     async recipeLevelTable(rlv: number): Promise<RecipeLevel> {
         const query = new URLSearchParams({ rlv: String(rlv) });
         const url = new URL('recipe_level_table', this.base);
@@ -139,6 +186,20 @@ export class WebSource {
         return resp.json();
     }
 
+    async recipe(recipeId: number): Promise<Recipe> {
+        const query = new URLSearchParams({ recipe_id: String(recipeId) });
+        const url = new URL('recipe', this.base);
+        url.search = query.toString();
+        const resp = await fetch(url, {
+            method: 'GET',
+            mode: 'cors',
+        });
+        if (!resp.ok) {
+            throw resp.statusText;
+        }
+        return resp.json();
+    }
+
     async itemInfo(itemId: number): Promise<Item> {
         const query = new URLSearchParams({ item_id: String(itemId) });
         const url = new URL('item_info', this.base);
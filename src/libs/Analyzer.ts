@@ -15,7 +15,12 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 import { isTauri } from './Consts';
-import { Actions, CollectablesShopRefine, Status } from './Craft';
+import {
+    Actions,
+    CollectablesMetadata,
+    CollectablesShopRefine,
+    Status,
+} from './Craft';
 
 if (isTauri) {
     // Good, the user is using our Desktop edition. Use the native solvers.
@@ -33,6 +38,37 @@ export interface Statistics {
     normal: number;
     // 进展推满，品质也推满的模拟频数
     highqual: number;
+    // 出HQ的概率及其95%置信区间
+    highqual_rate: ConfidenceInterval;
+    // 进展推满的模拟的最终品质
+    quality: Histogram;
+    // 进展推满的模拟剩余的制作力
+    craft_points_left: Histogram;
+    // 进展推满的模拟剩余的耐久
+    durability_left: Histogram;
+    // 未发生技能错误的模拟所用的步数
+    steps: Histogram;
+}
+
+export interface Histogram {
+    count: number;
+    mean: number | null;
+    std_dev: number | null;
+    min: number | null;
+    max: number | null;
+    p5: number | null;
+    p25: number | null;
+    p50: number | null;
+    p75: number | null;
+    p95: number | null;
+    // [数值, 频数]，按数值从小到大排列
+    buckets: [number, number][];
+}
+
+export interface ConfidenceInterval {
+    rate: number;
+    lower: number;
+    upper: number;
 }
 
 export interface CollectableStatistics {
@@ -50,6 +86,14 @@ export interface CollectableStatistics {
     middle_collectability: number;
     // 收藏价值第三档
     high_collectability: number;
+    // 每次制作的期望奖励，只在收藏品信息中包含奖励时计算
+    expected_reward: ExpectedReward | null;
+}
+
+export interface ExpectedReward {
+    scrip: number;
+    exp: number;
+    points: number;
 }
 
 export async function rand_simulation(
@@ -86,9 +130,9 @@ export async function rand_collectables_simulation(
     actions: Actions[],
     n: number,
     ignoreErrors: boolean,
-    collectablesShopRefine: CollectablesShopRefine,
+    collectablesMetadata: CollectablesMetadata,
 ): Promise<CollectableStatistics> {
-    const args = { status, actions, n, ignoreErrors, collectablesShopRefine };
+    const args = { status, actions, n, ignoreErrors, collectablesMetadata };
     if (isTauri) {
         let { invoke } = await pkgTauri;
         return invoke('rand_collectables_simulation', args);
@@ -115,6 +159,19 @@ export interface Scope {
     craftsmanship_range: [number?, number?];
     control_range: number | null;
     craft_points: number;
+    // 仍能得到相同制作结果的最低制作力上限
+    min_craft_points: number;
+    // 加工精度超过该值后，品质在最后一次提升品质的技能之前就已经达到上限
+    control_upper_bound: number | null;
+    // 每个技能的进展和品质增量保持不变的属性范围
+    steps: StepScope[];
+}
+
+export interface StepScope {
+    progress: number;
+    quality: number;
+    craftsmanship_range: [number, number | null];
+    control_range: [number, number | null];
 }
 
 export async function calc_attributes_scope(
@@ -143,3 +200,124 @@ export async function calc_attributes_scope(
         });
     }
 }
+
+export interface Axis {
+    min: number;
+    max: number;
+    step: number;
+}
+
+export type Outcome =
+    | 'Fail'
+    | 'Normal'
+    | 'HighQuality'
+    | 'NoCollectability'
+    | 'LowCollectability'
+    | 'MiddleCollectability'
+    | 'HighCollectability';
+
+export interface FeasibilityMap {
+    craftsmanship: number[];
+    control: number[];
+    craft_points: number;
+    // outcomes[i][j]为作业精度取craftsmanship[i]、加工精度取control[j]时的结果
+    outcomes: Outcome[][];
+}
+
+export async function feasibility_map(
+    status: Status,
+    actions: Actions[],
+    craftsmanship: Axis,
+    control: Axis,
+    craftPoints?: number,
+    collectablesShopRefine?: CollectablesShopRefine,
+): Promise<FeasibilityMap> {
+    const args = {
+        status,
+        actions,
+        craftsmanship,
+        control,
+        craftPoints,
+        collectablesShopRefine,
+    };
+    if (isTauri) {
+        let { invoke } = await pkgTauri;
+        return invoke('feasibility_map', args);
+    } else {
+        return new Promise((resolve, reject) => {
+            const worker = new Worker(
+                new URL('./AnalyzerWorker.ts', import.meta.url),
+                { type: 'module' },
+            );
+            worker.onmessage = ev => {
+                if (ev.data.error == undefined) resolve(ev.data);
+                else reject(ev.data.error);
+            };
+            worker.onerror = ev => reject(ev);
+            worker.postMessage({
+                name: 'feasibility_map',
+                args: JSON.stringify(args),
+            });
+        });
+    }
+}
+
+export interface Evaluation {
+    // 推满进展的概率
+    success_rate: number;
+    // 出HQ的概率
+    hq_rate: number;
+    // 最终品质的期望，进展未推满时记为0
+    expected_quality: number;
+    // 收藏价值档位（0~3）的期望，只在传入收藏品信息时计算
+    expected_collectability: number | null;
+}
+
+export interface StatWeight {
+    evaluation: Evaluation;
+    change: Evaluation;
+}
+
+export interface StatWeights {
+    delta: number;
+    base: Evaluation;
+    craftsmanship: StatWeight;
+    control: StatWeight;
+    craft_points: StatWeight;
+}
+
+export async function stat_weights(
+    status: Status,
+    actions: Actions[],
+    delta: number,
+    ignoreErrors: boolean,
+    collectablesShopRefine?: CollectablesShopRefine,
+): Promise<StatWeights> {
+    const args = {
+        status,
+        actions,
+        delta,
+        ignoreErrors,
+        collectablesShopRefine,
+    };
+    if (isTauri) {
+        let { invoke } = await pkgTauri;
+        return invoke('stat_weights', args);
+    } else {
+        return new Promise((resolve, reject) => {
+            const worker = new Worker(
+                new URL('./AnalyzerWorker.ts', import.meta.url),
+                { type: 'module' },
+            );
+            worker.onmessage = ev => {
+                if (ev.data.error == undefined) resolve(ev.data);
+                else reject(ev.data.error);
+            };
+            worker.onerror = ev => reject(ev);
+            worker.postMessage({
+                name: 'stat_weights',
+                args: JSON.stringify(args),
+            });
+        });
+    }
+}
@@ -20,6 +20,8 @@ onmessage = async e => {
             rand_simulation,
             rand_collectables_simulation,
             calc_attributes_scope,
+            feasibility_map,
+            stat_weights,
         } = await import('../../pkg-wasm/app_wasm');
     } else return;
     const { name, args: argsJson } = e.data;
@@ -43,11 +45,34 @@ onmessage = async e => {
                         args.actions,
                         args.n,
                         args.ignoreErrors,
-                        args.collectablesShopRefine,
+                        args.collectablesMetadata,
                     ),
                 );
             case 'calc_attributes_scope':
                 postMessage(calc_attributes_scope(args.status, args.actions));
+                break;
+            case 'feasibility_map':
+                postMessage(
+                    feasibility_map(
+                        args.status,
+                        args.actions,
+                        args.craftsmanship,
+                        args.control,
+                        args.craftPoints,
+                        args.collectablesShopRefine,
+                    ),
+                );
+                break;
+            case 'stat_weights':
+                postMessage(
+                    stat_weights(
+                        args.status,
+                        args.actions,
+                        args.delta,
+                        args.ignoreErrors,
+                        args.collectablesShopRefine,
+                    ),
+                );
         }
     } catch (e: any) {
         postMessage({ error: String(e) });
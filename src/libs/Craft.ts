@@ -274,6 +274,71 @@ export async function simulate(
     }
 }
 
+export type BuffKind =
+    | 'MuscleMemory'
+    | 'GreatStrides'
+    | 'Veneration'
+    | 'Innovation'
+    | 'FinalAppraisal'
+    | 'Manipulation'
+    | 'WasteNot'
+    | 'Expedience'
+    | 'HeartAndSoul'
+    | 'TrainedPerfection';
+
+export interface TraceStep {
+    pos: number;
+    action: Actions;
+    status: Status;
+    progress_delta: number;
+    quality_delta: number;
+    durability_delta: number;
+    craft_points_delta: number;
+    buffs_started: BuffKind[];
+    buffs_expired: BuffKind[];
+    buffs_consumed: BuffKind[];
+    skipped: boolean;
+    error: string | null;
+}
+
+export interface SimulateTraceResult {
+    status: Status;
+    steps: TraceStep[];
+}
+
+export async function simulateTrace(
+    status: Status,
+    actions: Actions[],
+): Promise<SimulateTraceResult> {
+    if (isTauri) {
+        let { invoke } = await pkgTauri;
+        return invoke('simulate_trace', { status, actions });
+    } else {
+        let { simulate_trace } = await pkgWasm;
+        return simulate_trace(status, actions);
+    }
+}
+
+export interface ScriptedStep {
+    action: Actions;
+    condition: Conditions;
+    // 只有坯料加工、仓促和冒进可以指定为失败，否则会以"action-cannot-fail: <pos>"失败
+    success?: boolean;
+}
+
+export async function simulateScripted(
+    status: Status,
+    script: ScriptedStep[],
+): Promise<SimulateTraceResult> {
+    if (isTauri) {
+        let { invoke } = await pkgTauri;
+        return invoke('simulate_scripted', { status, script });
+    } else {
+        let { simulate_scripted } = await pkgWasm;
+        return simulate_scripted(status, script);
+    }
+}
+
 export interface SimulateOneStepResult {
     status: Status;
     is_success: boolean;
@@ -305,6 +370,64 @@ export async function highQualityProbability(
     }
 }
 
+export interface Ingredient {
+    item_level: number;
+    can_be_hq: boolean;
+    amount: number;
+    hq_amount: number;
+}
+
+export interface HqIngredientsPlan {
+    // 与传入的素材一一对应的HQ素材数量
+    hq_amounts: number[];
+    initial_quality: number;
+}
+
+// 根据各素材中HQ素材的数量计算初期品质
+export async function initialQuality(
+    recipe: Recipe,
+    materialQualityFactor: number,
+    ingredients: Ingredient[],
+): Promise<number> {
+    if (isTauri) {
+        let { invoke } = await pkgTauri;
+        return invoke('initial_quality', {
+            recipe,
+            materialQualityFactor,
+            ingredients,
+        });
+    } else {
+        let { initial_quality } = await pkgWasm;
+        return initial_quality(recipe, materialQualityFactor, ingredients);
+    }
+}
+
+// 计算执行技能序列推满品质至少需要的HQ素材，无论如何都无法推满时返回null
+export async function requiredHqIngredients(
+    status: Status,
+    actions: Actions[],
+    materialQualityFactor: number,
+    ingredients: Ingredient[],
+): Promise<HqIngredientsPlan | null> {
+    if (isTauri) {
+        let { invoke } = await pkgTauri;
+        return invoke('required_hq_ingredients', {
+            status,
+            actions,
+            materialQualityFactor,
+            ingredients,
+        });
+    } else {
+        let { required_hq_ingredients } = await pkgWasm;
+        return required_hq_ingredients(
+            status,
+            actions,
+            materialQualityFactor,
+            ingredients,
+        );
+    }
+}
+
 export async function allowedList(
     status: Status,
     actions: Actions[],
@@ -362,6 +485,11 @@ export interface RecipeInfo {
     can_hq: boolean;
 }
 
+export interface IngredientUsage extends RecipeInfo {
+    // 每次制作消耗该物品的数量
+    amount: number;
+}
+
 export interface RecipeRequirements {
     required_craftsmanship: number;
     required_control: number;
@@ -372,9 +500,66 @@ export interface ItemWithAmount {
     amount: number;
 }
 
+export interface BomNode {
+    item_id: number;
+    name: string;
+    amount: number;
+    job: string | null;
+    // 为得到amount个成品需要制作的次数
+    crafts: number;
+    ingredients: BomNode[];
+}
+
+export interface BomMaterial {
+    item_id: number;
+    name: string;
+    amount: number;
+}
+
+export interface BomCraft {
+    item_id: number;
+    name: string;
+    recipe_id: number;
+    job: string;
+    crafts: number;
+    // 需要的成品数量
+    amount: number;
+    // 按配方的产量制作后多出的成品数量
+    wasted: number;
+}
+
+export interface Bom {
+    // 每个目标物品的展开树，各分支分别按产量取整
+    tree: BomNode[];
+    // 合并所有分支后需要准备的原材料
+    materials: BomMaterial[];
+    // 按先制作半成品、后制作成品的顺序排列的制作步骤
+    crafts: BomCraft[];
+}
+
 export interface CollectablesShopRefine {
     id: number;
     low_collectability: number;
     mid_collectability: number;
     high_collectability: number;
 }
+
+export type CollectablesMetadataKind =
+    | 'CollectablesShopRefine'
+    | 'HWDCrafterSupply'
+    | 'SatisfactionSupply'
+    | 'SharlayanCraftWorkSupply'
+    | 'CollectablesRefine';
+
+export interface CollectableReward {
+    scrip: number;
+    exp: number;
+    points: number;
+}
+
+// 任意一种收藏品信息，阈值字段与CollectablesShopRefine相同
+export interface CollectablesMetadata extends CollectablesShopRefine {
+    kind?: CollectablesMetadataKind;
+    // 依次为第一至第三档的奖励
+    rewards?: [CollectableReward, CollectableReward, CollectableReward] | null;
+}
@@ -14,8 +14,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+import { isTauri } from './Consts';
 import { Attributes } from './Craft';
 
+if (import.meta.env.VITE_BESTCRAFT_TARGET == 'tauri') {
+    var pkgTauri = import('@tauri-apps/api/core');
+} else {
+    var pkgWasm = import('@/../pkg-wasm/app_wasm');
+}
+
 export interface Enhancer {
     name: string;
     level?: number;
@@ -67,3 +74,22 @@ export function calculateEnhancedAttributs(
         { cm, ct, cp },
     ];
 }
+
+export interface EnhancedAttributes {
+    attributes: Attributes;
+    bonus: { cm: number; ct: number; cp: number };
+}
+
+// 与calculateEnhancedAttributs相同，但由app_libs计算，保证与求解器和分析器的结果一致
+export async function applyEnhancers(
+    attributes: Attributes,
+    enhancers: Enhancer[],
+): Promise<EnhancedAttributes> {
+    if (isTauri) {
+        let { invoke } = await pkgTauri;
+        return invoke('apply_enhancers', { attributes, enhancers });
+    } else {
+        let { apply_enhancers } = await pkgWasm;
+        return apply_enhancers(attributes, enhancers);
+    }
+}
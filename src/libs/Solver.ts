@@ -15,7 +15,8 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 import { isTauri } from './Consts';
-import { Actions, Status } from './Craft';
+import { Actions, Attributes, Recipe, SimulateResult, Status } from './Craft';
+import { Enhancer } from './Enhancer';
 import { clarityReport } from './Utils';
 
 export let supported = true;
@@ -23,70 +24,214 @@ export let supported = true;
 if (isTauri) {
     // Good, the user is using our Desktop edition. Use the native solvers.
     var pkgTauri = import('@tauri-apps/api/core');
+    var pkgTauriEvent = import('@tauri-apps/api/event');
 } else {
     // They are using the Web edition. Only wasm solvers could be used.
     // Check if the browser supports Web Worker.
     if (!window.Worker) supported = false;
-    var invokeWasmSolver = (name: string, args: any): Promise<Actions[]> => {
-        return new Promise((resolve, reject) => {
-            const worker = new Worker(
-                new URL('./SolverWorker.ts', import.meta.url),
-                {
-                    type: 'module',
-                },
-            );
+    // 正在运行的Worker，取消求解时直接终止它们
+    var runningWorkers = new Map<Worker, (reason: any) => void>();
+    var invokeWasmSolver = <T = Actions[]>(
+        name: string,
+        args: any,
+    ): Promise<T> => {
+        const worker = new Worker(
+            new URL('./SolverWorker.ts', import.meta.url),
+            {
+                type: 'module',
+            },
+        );
+        return new Promise<T>((resolve, reject) => {
+            runningWorkers.set(worker, reject);
             worker.onmessage = ev => {
                 if (ev.data.error == undefined) resolve(ev.data);
                 else reject(ev.data.error);
             };
             worker.onerror = ev => reject(ev);
             worker.postMessage({ name, args: JSON.stringify(args) });
-        });
+        }).finally(() => runningWorkers.delete(worker));
     };
 }
 
+export interface SolverEvent<T> {
+    solver: string;
+    value: T;
+}
+
+// 中止所有正在运行的求解器，被中止的求解会以"solve-cancelled"失败
+export async function cancel_solve() {
+    if (isTauri) {
+        let { invoke } = await pkgTauri;
+        await invoke('cancel_solve');
+    } else {
+        for (const [worker, reject] of runningWorkers) {
+            worker.terminate();
+            reject('solve-cancelled');
+        }
+        runningWorkers.clear();
+    }
+}
+
+// 监听求解器的进度和中间结果，返回取消监听的函数。只有桌面端会产生这些事件
+export async function listen_solver_events(
+    onProgress: (event: SolverEvent<number>) => void,
+    onIntermediate: (event: SolverEvent<Actions[]>) => void,
+): Promise<() => void> {
+    if (!isTauri) return () => {};
+    const { listen } = await pkgTauriEvent;
+    const unlisteners = await Promise.all([
+        listen<SolverEvent<number>>('solver-progress', e =>
+            onProgress(e.payload),
+        ),
+        listen<SolverEvent<Actions[]>>('solver-intermediate', e =>
+            onIntermediate(e.payload),
+        ),
+    ]);
+    return () => unlisteners.forEach(unlisten => unlisten());
+}
+
+// 在后台建立动态规划求解器，返回任务ID，建好后任务状态为'finished'
 export async function create_solver(
     status: Status,
     useMuscleMemory: boolean,
     useManipulation: boolean,
     useObserve: boolean,
-) {
+): Promise<number> {
     let { invoke } = await pkgTauri;
     return invoke('create_solver', {
         status,
-        useMuscleMemory,
-        useManipulation,
-        useObserve,
+        config: {
+            use_muscle_memory: useMuscleMemory,
+            use_manipulation: useManipulation,
+            use_observe: useObserve,
+        },
     });
 }
 
-export async function destroy_solver(status: Status) {
+export async function destroy_solver(id: number) {
     if (isTauri) {
         let { invoke } = await pkgTauri;
-        invoke('destroy_solver', { status });
+        await invoke('destroy_solver', { id });
     } else {
         throw 'solver-doesn-t-exist';
     }
 }
 
-export async function read_solver(status: Status): Promise<Actions[]> {
+// 从建好的求解器读取结果，status的属性和配方需要与建立求解器时相同
+export async function read_solver(
+    id: number,
+    status: Status,
+): Promise<Actions[]> {
     if (isTauri) {
         let { invoke } = await pkgTauri;
-        return invoke('read_solver', { status });
+        return invoke('read_solver', { id, status });
     } else {
         throw 'solver-doesn-t-exist';
     }
 }
 
-export async function rika_solve(status: Status): Promise<Actions[]> {
-    clarityReport('runRikaSolver');
+// 求解器及其全部参数，与`app_libs::solver::registry::SolverConfig`对应
+export type SolverConfig =
+    | {
+          name: 'raphael';
+          // 不填时以配方的最高品质为目标
+          target_quality?: number | null;
+          use_manipulation: boolean;
+          use_heart_and_soul: boolean;
+          use_quick_innovation: boolean;
+          use_trained_eye: boolean;
+          backload_progress: boolean;
+          adversarial: boolean;
+          unsound_branch_pruning: boolean;
+      }
+    | { name: 'depth_first_search'; depth: number; specialist: boolean }
+    | { name: 'normal_progress'; depth: number; specialist: boolean }
+    | {
+          name: 'reflect';
+          use_manipulation: boolean;
+          use_waste_not: number;
+          use_observe: boolean;
+      }
+    | { name: 'rika' }
+    | {
+          name: 'rika_tnze';
+          use_manipulation: boolean;
+          use_waste_not: number;
+          use_observe: boolean;
+          reduce_steps: boolean;
+      };
+
+export type SolverTarget = 'max_quality' | 'target_quality' | 'progress_only';
+
+// 描述一个求解器的能力
+export interface SolverInfo {
+    name: SolverConfig['name'];
+    // 桌面端是否可用
+    native: boolean;
+    // 网页版是否可用
+    wasm: boolean;
+    // 能否从制作中途的状态继续求解
+    mid_craft: boolean;
+    targets: SolverTarget[];
+    // 可以通过参数禁止使用的技能
+    restrictable_actions: Actions[];
+}
+
+// 列出所有求解器及其支持的功能
+export async function solver_list(): Promise<SolverInfo[]> {
     if (isTauri) {
-        return (await pkgTauri).invoke('rika_solve', { status });
+        return (await pkgTauri).invoke('solver_list');
     } else {
-        return invokeWasmSolver('rika_solve', { status });
+        return invokeWasmSolver<SolverInfo[]>('solver_list', {});
     }
 }
 
+// 所有求解器的统一入口
+export async function solve(
+    status: Status,
+    config: SolverConfig,
+): Promise<Actions[]> {
+    if (isTauri) {
+        return (await pkgTauri).invoke('solve', { status, config });
+    } else {
+        return invokeWasmSolver('solve', { status, config });
+    }
+}
+
+export type PortfolioObjective = 'score' | 'quality' | 'steps';
+
+export interface PortfolioEntry {
+    config: SolverConfig;
+    actions: Actions[];
+    // 按求解结果模拟得到的最终状态
+    status: Status;
+    errors: SimulateResult['errors'];
+    // 模拟没有出错并且推满了进展
+    valid: boolean;
+    // 求解器无法运行或超时的原因，例如"solve-timeout"
+    error: string | null;
+    // 毫秒
+    elapsed: number;
+}
+
+// 在timeLimit毫秒内用多个求解器同时求解，返回按objective从好到坏排列的全部结果，只在桌面端可用
+export async function solve_portfolio(
+    status: Status,
+    configs: SolverConfig[],
+    timeLimit: number,
+    objective?: PortfolioObjective,
+): Promise<PortfolioEntry[]> {
+    if (!isTauri) throw 'unsupported';
+    clarityReport('runPortfolioSolver');
+    let { invoke } = await pkgTauri;
+    return invoke('solve_portfolio', { status, configs, timeLimit, objective });
+}
+
+export async function rika_solve(status: Status): Promise<Actions[]> {
+    clarityReport('runRikaSolver');
+    return solve(status, { name: 'rika' });
+}
+
 export async function rika_solve_tnzever(
     status: Status,
     useManipulation: boolean,
@@ -95,18 +240,13 @@ export async function rika_solve_tnzever(
     reduceSteps: boolean,
 ): Promise<Actions[]> {
     clarityReport('runRikaSolverTnzeVer');
-    if (isTauri) {
-        let { invoke } = await pkgTauri;
-        return invoke('rika_solve_tnzever', {
-            status,
-            useManipulation,
-            useWastNot,
-            useObserve,
-            reduceSteps,
-        });
-    } else {
-        throw 'solver-doesn-t-exist';
-    }
+    return solve(status, {
+        name: 'rika_tnze',
+        use_manipulation: useManipulation,
+        use_waste_not: useWastNot,
+        use_observe: useObserve,
+        reduce_steps: reduceSteps,
+    });
 }
 
 export async function dfs_solve(
@@ -115,12 +255,7 @@ export async function dfs_solve(
     specialist: boolean,
 ): Promise<Actions[]> {
     clarityReport('runDfsSolver');
-    const args = { status, depth, specialist };
-    if (isTauri) {
-        return (await pkgTauri).invoke('dfs_solve', args);
-    } else {
-        return invokeWasmSolver('dfs_solve', args);
-    }
+    return solve(status, { name: 'depth_first_search', depth, specialist });
 }
 
 export async function nq_solve(
@@ -129,12 +264,7 @@ export async function nq_solve(
     specialist: boolean,
 ): Promise<Actions[]> {
     clarityReport('runNqSolver');
-    const args = { status, depth, specialist };
-    if (isTauri) {
-        return (await pkgTauri).invoke('nq_solve', args);
-    } else {
-        return invokeWasmSolver('nq_solve', args);
-    }
+    return solve(status, { name: 'normal_progress', depth, specialist });
 }
 
 /// 基于DP的闲静手法求解
@@ -147,17 +277,13 @@ export async function reflect_solve(
     useObserve: boolean,
 ): Promise<Actions[]> {
     clarityReport('runReflectSolver');
-    if (isTauri) {
-        let { invoke } = await pkgTauri;
-        return invoke('reflect_solve', {
-            status,
-            useManipulation,
-            useWasteNot,
-            useObserve,
-        });
-    } else {
-        return invokeWasmSolver('reflect_solve', { status, useObserve });
-    }
+    // 网页版中内存有限，不使用掌握和俭约
+    return solve(status, {
+        name: 'reflect',
+        use_manipulation: isTauri && useManipulation,
+        use_waste_not: isTauri ? useWasteNot : 0,
+        use_observe: useObserve,
+    });
 }
 
 export async function raphael_solve(
@@ -172,21 +298,165 @@ export async function raphael_solve(
     unsoundBranchPruning: boolean,
 ): Promise<Actions[]> {
     clarityReport('runRaphaelSolver');
-    const args = {
-        status,
-        targetQuality,
-        useManipulation,
-        useHeartAndSoul,
-        useQuickInnovation,
-        useTrainedEye,
-        backloadProgress,
+    return solve(status, {
+        name: 'raphael',
+        target_quality: targetQuality,
+        use_manipulation: useManipulation,
+        use_heart_and_soul: useHeartAndSoul,
+        use_quick_innovation: useQuickInnovation,
+        use_trained_eye: useTrainedEye,
+        backload_progress: backloadProgress,
         adversarial,
-        unsoundBranchPruning,
+        unsound_branch_pruning: unsoundBranchPruning,
+    });
+}
+
+export interface SolveJobStatus {
+    state: 'queued' | 'running' | 'finished' | 'cancelled';
+    progress: number;
+    intermediate: Actions[] | null;
+    // 毫秒
+    elapsed: number | null;
+}
+
+// 提交一个后台求解任务，返回任务ID
+export async function start_solve(
+    status: Status,
+    config: SolverConfig,
+): Promise<number> {
+    if (!isTauri) throw 'unsupported';
+    let { invoke } = await pkgTauri;
+    return invoke('start_solve', { status, config });
+}
+
+export async function poll_solve(id: number): Promise<SolveJobStatus> {
+    let { invoke } = await pkgTauri;
+    return invoke('poll_solve', { id });
+}
+
+export async function cancel_solve_job(id: number) {
+    let { invoke } = await pkgTauri;
+    await invoke('cancel_solve_job', { id });
+}
+
+// 取出已结束的任务的结果，之后该任务会被移除
+export async function solve_result(id: number): Promise<Actions[]> {
+    let { invoke } = await pkgTauri;
+    return invoke('solve_result', { id });
+}
+
+export async function set_max_concurrent_solves(max: number) {
+    if (!isTauri) return;
+    let { invoke } = await pkgTauri;
+    await invoke('set_max_concurrent_solves', { max });
+}
+
+// 提交后台求解任务并定期查询状态，直到任务结束后返回结果
+export async function run_solve_job(
+    status: Status,
+    config: SolverConfig,
+    onPoll?: (status: SolveJobStatus) => void,
+    interval = 500,
+): Promise<Actions[]> {
+    const id = await start_solve(status, config);
+    await wait_solve_job(id, onPoll, interval);
+    return solve_result(id);
+}
+
+// 定期查询任务状态，直到任务结束后返回最后的状态
+export async function wait_solve_job(
+    id: number,
+    onPoll?: (status: SolveJobStatus) => void,
+    interval = 500,
+): Promise<SolveJobStatus> {
+    for (;;) {
+        const jobStatus = await poll_solve(id);
+        onPoll?.(jobStatus);
+        if (jobStatus.state == 'finished' || jobStatus.state == 'cancelled')
+            return jobStatus;
+        await new Promise(resolve => setTimeout(resolve, interval));
+    }
+}
+
+export type ExpertObjective = 'success' | 'high_quality';
+
+export interface ExpertSuggestion {
+    action: Actions;
+    // 释放该技能后目标的期望值，即估计的成功率或HQ率
+    value: number;
+}
+
+// 高难度配方的逐步求解，根据包含球色在内的当前状态给出下一步的技能
+// depth: 向后搜索的步数，一般取2即可
+export async function expert_solve(
+    status: Status,
+    depth: number,
+    specialist: boolean,
+    objective: ExpertObjective,
+): Promise<ExpertSuggestion | null> {
+    clarityReport('runExpertSolver');
+    const args = { status, depth, specialist, objective };
+    if (isTauri) {
+        return (await pkgTauri).invoke('expert_solve', args);
+    } else {
+        return invokeWasmSolver<ExpertSuggestion | null>('expert_solve', args);
+    }
+}
+
+export type EnhancerTarget =
+    | { kind: 'high_quality' }
+    | { kind: 'collectability'; value: number };
+
+export interface EnhancerPlan {
+    meal: Enhancer | null;
+    medicine: Enhancer | null;
+    attributes: Attributes;
+    actions: Actions[];
+    // 按求解结果模拟得到的最终状态
+    status: Status;
+    reached: boolean;
+}
+
+export interface OptimizeEnhancersResult {
+    // 不使用任何食物和药水时的求解结果
+    without: EnhancerPlan;
+    // 能达成目标的最便宜的组合
+    best: EnhancerPlan | null;
+    // 预算用尽时仍有未尝试的组合
+    exhausted: boolean;
+}
+
+export interface EnhancerBudget {
+    // 最多为多少个组合调用求解器
+    max_solves: number;
+    // 网页版无法计时，会忽略这一项
+    time_limit_ms: number | null;
+}
+
+// 寻找能达成制作目标的最便宜的食物和药水组合，被更便宜的组合支配的候选不会调用求解器
+export async function optimize_enhancers(
+    attributes: Attributes,
+    recipe: Recipe,
+    meals: Enhancer[],
+    medicines: Enhancer[],
+    target: EnhancerTarget,
+    solver: SolverConfig,
+    budget: EnhancerBudget,
+): Promise<OptimizeEnhancersResult> {
+    clarityReport('runEnhancerOptimizer');
+    const args = {
+        attributes,
+        recipe,
+        meals,
+        medicines,
+        options: { target, solver, budget },
     };
     if (isTauri) {
-        let { invoke } = await pkgTauri;
-        return invoke('raphael_solve', args);
+        return (await pkgTauri).invoke('optimize_enhancers', args);
     } else {
-        return invokeWasmSolver('raphael_solve', args);
+        return invokeWasmSolver<OptimizeEnhancersResult>(
+            'optimize_enhancers',
+            args,
+        );
     }
 }
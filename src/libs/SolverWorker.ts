@@ -1,38 +1,40 @@
 onmessage = async e => {
     if (import.meta.env.VITE_BESTCRAFT_TARGET == 'web') {
-        var { dfs_solve, nq_solve, rika_solve, reflect_solve, raphael_solve } =
-            await import('../../pkg-wasm/app_wasm');
+        var {
+            solve,
+            solver_list,
+            expert_solve,
+            optimize_enhancers,
+        } = await import('../../pkg-wasm/app_wasm');
     } else return;
     const { name, args: argsJson } = e.data;
     const args = JSON.parse(argsJson);
     try {
         var result;
         switch (name) {
-            case 'dfs_solve':
-            case 'nq_solve':
-                let solve = name == 'dfs_solve' ? dfs_solve : nq_solve;
-                result = solve(args.status, args.depth, args.specialist);
+            case 'solve':
+                result = solve(args.status, args.config);
                 break;
-            case 'rika_solve':
-                result = rika_solve(args.status);
+            case 'solver_list':
+                result = solver_list();
                 break;
-            case 'reflect_solve':
-                result = reflect_solve(args.status, args.useObserve);
-                break;
-            case 'rika_solve_tnzever':
-                throw 'unsupported';
-            case 'raphael_solve':
-                result = raphael_solve(
+            case 'expert_solve':
+                result = expert_solve(
                     args.status,
-                    args.targetQuality,
-                    args.useManipulation,
-                    args.useHeartAndSoul,
-                    args.useQuickInnovation,
-                    args.useTrainedEye,
-                    args.backloadProgress,
-                    args.adversarial,
-                    args.unsoundBranchPruning,
+                    args.depth,
+                    args.specialist,
+                    args.objective,
+                );
+                break;
+            case 'optimize_enhancers':
+                result = optimize_enhancers(
+                    args.attributes,
+                    args.recipe,
+                    args.meals,
+                    args.medicines,
+                    args.options,
                 );
+                break;
         }
         postMessage(result);
     } catch (e: any) {